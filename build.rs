@@ -1,264 +1,261 @@
-use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::Path;
 
+#[path = "src/schema.rs"]
+mod schema;
+
+use schema::{PatternData, is_pattern, needs_magic, significant_bits};
+
+/// Formats bytes as a Rust slice literal.
+fn byte_slice(bytes: &[u8]) -> String {
+    let items: Vec<String> = bytes.iter().map(|b| format!("0x{:02X}", b)).collect();
+    format!("&[{}]", items.join(", "))
+}
+
+fn mask_literal(mask: &Option<Vec<u8>>) -> String {
+    match mask {
+        Some(mask) => format!("Some({})", byte_slice(mask)),
+        None => "None".to_string(),
+    }
+}
+
+/// Converts a format id to its `Format` variant name, e.g. `seven_zip` to
+/// `SevenZip`.
+fn variant_name(id: &str) -> String {
+    id.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
 fn main() {
     let out_dir = env::var("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("extensions.rs");
 
     println!("cargo:rerun-if-changed=archive_signatures.yaml");
+    println!("cargo:rerun-if-changed=src/schema.rs");
 
     let content = fs::read_to_string("archive_signatures.yaml")
         .expect("Failed to read archive_signatures.yaml");
 
-    // Categories Mapping
-    let category_map: HashMap<&str, &str> = [
-        ("archiveOnly", "archive/storage"),
-        ("compressOnly", "archive/stream-compression"),
-        ("archiveAndCompress", "archive/compressed-archive"),
-        ("dataRecovery", "archive/recovery"),
-        ("packaging", "archive/package"),
-        ("containers", "archive/container"),
-    ]
-    .iter()
-    .cloned()
-    .collect();
-
-    #[derive(Clone)]
-    struct SigData {
-        bytes: Vec<u8>,
-        offset: usize,
-        description: String,
-        hexdump_str: String,
-    }
+    let entries =
+        schema::parse(&content).unwrap_or_else(|err| panic!("archive_signatures.yaml: {}", err));
 
-    struct Entry {
-        ext: String,
-        category_mime: String,
-        signatures: Vec<SigData>,
-    }
-
-    let mut entries: Vec<Entry> = Vec::new();
-
-    let mut current_category_mime = String::new();
-    let mut current_ext = String::new();
-
-    struct PendingSig {
-        bytes: Option<Vec<u8>>,
-        offset: usize,
-        description: String,
-        hexdump_str: String,
-    }
-    let mut current_sig: Option<PendingSig> = None;
+    // --- Generate Code ---
+    let mut output = String::new();
+    // VERSION 4
+    output.push_str("use crate::ArchiveInfo;");
+    output.push('\n');
+    output.push_str("use crate::magic::{BytePattern, MagicSignature};");
+    output.push('\n');
+    output.push('\n');
 
-    for line in content.lines() {
-        let line = line.trim();
-        if line.is_empty() {
+    // One variant per distinct format id, from keys and signatures alike.
+    let mut formats: Vec<(String, String)> = Vec::new();
+    let ids = entries.iter().flat_map(|entry| {
+        std::iter::once(&entry.format).chain(entry.signatures.iter().map(|sig| &sig.format))
+    });
+    for id in ids {
+        if formats.iter().any(|(known, _)| known == id) {
             continue;
         }
-
-        if line.ends_with(':')
-            && !line.starts_with("hexdump")
-            && !line.starts_with("offset")
-            && !line.starts_with("description")
-            && !line.starts_with("- description")
-        {
-            // Push pending
-            if let Some(s) = current_sig.take()
-                && let Some(bytes) = s.bytes
-                && bytes.len() >= 2
-            {
-                if let Some(entry) = entries
-                    .iter_mut()
-                    .find(|e| e.ext == current_ext && e.category_mime == current_category_mime)
-                {
-                    entry.signatures.push(SigData {
-                        bytes,
-                        offset: s.offset,
-                        description: s.description,
-                        hexdump_str: s.hexdump_str,
-                    });
-                } else {
-                    entries.push(Entry {
-                        ext: current_ext.clone(),
-                        category_mime: current_category_mime.clone(),
-                        signatures: vec![SigData {
-                            bytes,
-                            offset: s.offset,
-                            description: s.description,
-                            hexdump_str: s.hexdump_str,
-                        }],
-                    });
-                }
-            }
-
-            let key = line.trim_end_matches(':');
-            if let Some(mime) = category_map.get(key) {
-                current_category_mime = mime.to_string();
-            } else if key.starts_with('.') {
-                current_ext = key.to_string();
-            }
-        } else if line.starts_with("- description:") {
-            // Push pending
-            if let Some(s) = current_sig.take()
-                && let Some(bytes) = s.bytes
-                && bytes.len() >= 2
-            {
-                if let Some(entry) = entries
-                    .iter_mut()
-                    .find(|e| e.ext == current_ext && e.category_mime == current_category_mime)
-                {
-                    entry.signatures.push(SigData {
-                        bytes,
-                        offset: s.offset,
-                        description: s.description,
-                        hexdump_str: s.hexdump_str,
-                    });
-                } else {
-                    entries.push(Entry {
-                        ext: current_ext.clone(),
-                        category_mime: current_category_mime.clone(),
-                        signatures: vec![SigData {
-                            bytes,
-                            offset: s.offset,
-                            description: s.description,
-                            hexdump_str: s.hexdump_str,
-                        }],
-                    });
-                }
-            }
-
-            let desc = line
-                .trim_start_matches("- description:")
-                .trim()
-                .trim_matches('"')
-                .to_string();
-            let desc_escaped = desc.replace("\"", "\\\"");
-
-            current_sig = Some(PendingSig {
-                bytes: None,
-                offset: 0,
-                description: desc_escaped,
-                hexdump_str: String::new(),
-            });
-        } else if line.starts_with("hexdump:") {
-            if let Some(s) = current_sig.as_mut()
-                && let Some(start) = line.find('"')
-                && let Some(end) = line.rfind('"')
-                && end > start
-            {
-                let hex_str = &line[start + 1..end];
-                s.hexdump_str = hex_str.to_string();
-                let bytes: Vec<u8> = hex_str
-                    .split_whitespace()
-                    .filter_map(|s| u8::from_str_radix(s, 16).ok())
-                    .collect();
-                s.bytes = Some(bytes);
-            }
-        } else if line.starts_with("offset:")
-            && let Some(s) = current_sig.as_mut()
-            && let Some(val_str) = line.strip_prefix("offset:").map(|s| s.trim())
-            && let Ok(val) = val_str.parse::<usize>()
-        {
-            s.offset = val;
+        let variant = variant_name(id);
+        if variant == "Custom" {
+            panic!("Format id custom is reserved for runtime signatures");
         }
-    }
-    // Push final
-    if let Some(s) = current_sig.take()
-        && let Some(bytes) = s.bytes
-        && bytes.len() >= 2
-    {
-        if let Some(entry) = entries
-            .iter_mut()
-            .find(|e| e.ext == current_ext && e.category_mime == current_category_mime)
-        {
-            entry.signatures.push(SigData {
-                bytes,
-                offset: s.offset,
-                description: s.description,
-                hexdump_str: s.hexdump_str,
-            });
-        } else {
-            entries.push(Entry {
-                ext: current_ext.clone(),
-                category_mime: current_category_mime.clone(),
-                signatures: vec![SigData {
-                    bytes,
-                    offset: s.offset,
-                    description: s.description,
-                    hexdump_str: s.hexdump_str,
-                }],
-            });
+        if let Some((other, _)) = formats.iter().find(|(_, v)| *v == variant) {
+            panic!(
+                "Format ids {} and {} both map to Format::{}",
+                other, id, variant
+            );
         }
+        formats.push((id.clone(), variant));
+    }
+    formats.sort_by(|a, b| a.1.cmp(&b.1));
+
+    output.push_str("/// The file format a detection result belongs to.\n");
+    output.push_str("///\n");
+    output.push_str("/// There is one variant per format id in `archive_signatures.yaml`.\n");
+    output
+        .push_str("/// Variants are added as the database grows, so matches on `Format` need a\n");
+    output.push_str("/// wildcard arm.\n");
+    output.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\n");
+    output.push_str("#[non_exhaustive]\n");
+    output.push_str("pub enum Format {\n");
+    for (id, variant) in &formats {
+        output.push_str(&format!("    /// `{}`\n", id));
+        output.push_str(&format!("    {},\n", variant));
+    }
+    output
+        .push_str("    /// A format loaded at runtime by a [`SignatureDb`](crate::SignatureDb),\n");
+    output.push_str("    /// under an extension the built-in database does not list.\n");
+    output.push_str("    Custom,\n");
+    output.push_str("}\n\n");
+
+    output.push_str("impl Format {\n");
+    output
+        .push_str("    /// Every built-in format, ordered by name. Excludes [`Format::Custom`].\n");
+    output.push_str("    pub const ALL: &[Format] = &[\n");
+    for (_, variant) in &formats {
+        output.push_str(&format!("        Format::{},\n", variant));
+    }
+    output.push_str("    ];\n\n");
+    output.push_str("    /// Stable identifier for the format, e.g. `\"seven_zip\"`.\n");
+    output.push_str("    ///\n");
+    output.push_str("    /// Unlike descriptions, IDs do not change when signatures are edited.\n");
+    output.push_str("    pub fn id(self) -> &'static str {\n");
+    output.push_str("        match self {\n");
+    for (id, variant) in &formats {
+        output.push_str(&format!("            Format::{} => {:?},\n", variant, id));
+    }
+    output.push_str("            Format::Custom => \"custom\",\n");
+    output.push_str("        }\n");
+    output.push_str("    }\n\n");
+    output.push_str("    /// Looks up a built-in format by its [`id`](Format::id).\n");
+    output.push_str("    pub fn from_id(id: &str) -> Option<Format> {\n");
+    output.push_str("        Format::ALL.iter().copied().find(|f| f.id() == id)\n");
+    output.push_str("    }\n");
+    output.push_str("}\n\n");
+
+    let mut categories: Vec<&str> = schema::SECTIONS.iter().map(|(_, c)| *c).collect();
+    categories.sort();
+    output
+        .push_str("/// Every category a detection result can belong to, e.g. `archive/package`.\n");
+    output.push_str("pub static CATEGORIES: &[&str] = &[\n");
+    for category in &categories {
+        output.push_str(&format!("    {:?},\n", category));
+    }
+    output.push_str("];\n\n");
+
+    let mime_types = schema::mime_types(&entries).unwrap_or_else(|err| panic!("{}", err));
+    let mime_literal = |format: &str| match mime_types.get(format) {
+        Some(mime_type) => format!("Some({:?})", mime_type),
+        None => "None".to_string(),
+    };
+
+    let ext_to_info = schema::extension_infos(&entries);
+    let sorted_exts: Vec<&String> = ext_to_info.keys().collect();
+    let (patterns, exact): (Vec<_>, Vec<_>) = sorted_exts.into_iter().partition(|e| is_pattern(e));
+
+    let extension_info = |ext: &str| {
+        let info = &ext_to_info[ext];
+        format!(
+            "ArchiveInfo {{ format: Format::{}, category: {:?}, mime_type: {}, description: {:?}, hexdump: \"\", extension: {:?}, extensions: &[{:?}], confidence: crate::magic::EXTENSION_CONFIDENCE, offset: None }}",
+            variant_name(&info.format),
+            info.category_mime,
+            mime_literal(&info.format),
+            info.description,
+            ext,
+            ext
+        )
+    };
+
+    // One row per format, listed under the key it is named after, else its
+    // first key, else the first key its signatures are filed under.
+    // (extension, variant, extensions, category, mime type, description,
+    // signature count)
+    type Row = (String, String, Vec<String>, String, String, String, usize);
+    let mut rows: Vec<Row> = Vec::new();
+    for (id, variant) in &formats {
+        let signatures: Vec<(&schema::Entry, &schema::SigData)> = entries
+            .iter()
+            .flat_map(|entry| entry.signatures.iter().map(move |sig| (entry, sig)))
+            .filter(|(_, sig)| sig.format == *id)
+            .collect();
+        let keys: Vec<(&String, &schema::KeyInfo)> = ext_to_info
+            .iter()
+            .filter(|(_, info)| info.format == *id)
+            .collect();
+        let named = keys
+            .iter()
+            .find(|(ext, _)| schema::key_format_id(ext).as_deref() == Some(id.as_str()));
+        let matchable = keys.iter().find(|(ext, _)| !needs_magic(ext));
+        let (ext, category, description) = match named.or(matchable).or(keys.first()) {
+            Some((ext, info)) => (
+                ext.to_string(),
+                info.category_mime.clone(),
+                info.description.clone(),
+            ),
+            None => {
+                let (entry, sig) = signatures
+                    .iter()
+                    .min_by_key(|(entry, _)| entry.ext.to_lowercase())
+                    .unwrap();
+                (
+                    entry.ext.to_lowercase(),
+                    entry.category_mime.clone(),
+                    sig.description.clone(),
+                )
+            }
+        };
+        // Every key the extension fallback recognizes, the listed one first.
+        let mut extensions: Vec<String> = keys
+            .iter()
+            .map(|(key, _)| key.to_string())
+            .filter(|key| !needs_magic(key))
+            .collect();
+        extensions.sort_by_key(|key| *key != ext);
+        rows.push((
+            ext,
+            variant.clone(),
+            extensions,
+            category,
+            mime_literal(id),
+            description,
+            signatures.len(),
+        ));
+    }
+    rows.sort();
+    output.push_str("pub static FORMATS: &[crate::FormatInfo] = &[\n");
+    for (ext, variant, extensions, category, mime, description, signatures) in &rows {
+        output.push_str(&format!(
+            "    crate::FormatInfo {{ format: Format::{}, extension: {:?}, extensions: &{:?}, category: {:?}, mime_type: {}, description: {:?}, signatures: {} }},\n",
+            variant, ext, extensions, category, mime, description, signatures
+        ));
     }
+    output.push_str("];\n\n");
 
-    // --- Generate Code ---
-    let mut output = String::new();
-    // VERSION 2
-    output.push_str("pub struct ArchiveInfo {");
+    // Glob keys are tried in order after every exact key failed to match.
+    // Loose ones are left out, their signatures have to match instead.
+    output.push_str("pub(crate) static EXTENSION_PATTERNS: &[(&str, ArchiveInfo)] = &[");
     output.push('\n');
-    output.push_str("    pub category: &'static str,");
+    for ext in patterns.into_iter().filter(|ext| !needs_magic(ext)) {
+        output.push_str(&format!("    ({:?}, {}),\n", ext, extension_info(ext)));
+    }
+    output.push_str("];");
     output.push('\n');
-    output.push_str("    pub description: &'static str,");
     output.push('\n');
-    output.push_str("    pub hexdump: &'static str,");
+
+    output.push_str("pub fn get_extension_info(ext: &str) -> Option<ArchiveInfo> {");
     output.push('\n');
-    output.push('}');
+    output.push_str("    let ext = ext.to_lowercase();");
     output.push('\n');
+    output.push_str("    match ext.as_str() {");
     output.push('\n');
 
-    let priority = [
-        "archive/package",
-        "archive/recovery",
-        "archive/compressed-archive",
-        "archive/stream-compression",
-        "archive/storage",
-        "archive/container",
-    ];
-
-    let mut ext_to_info: HashMap<String, (String, String)> = HashMap::new();
-    for entry in &entries {
-        let desc = if let Some(sig) = entry.signatures.first() {
-            sig.description.clone()
-        } else {
-            format!("{} archive", entry.ext)
-        };
-
-        let ext_lower = entry.ext.to_lowercase();
-
-        if let Some((existing_mime, _)) = ext_to_info.get(&ext_lower) {
-            let old_p = priority
-                .iter()
-                .position(|&p| p == existing_mime)
-                .unwrap_or(999);
-            let new_p = priority
-                .iter()
-                .position(|&p| p == entry.category_mime)
-                .unwrap_or(999);
-            if new_p < old_p {
-                ext_to_info.insert(ext_lower, (entry.category_mime.clone(), desc));
-            }
-        } else {
-            ext_to_info.insert(ext_lower, (entry.category_mime.clone(), desc));
-        }
+    for ext in exact {
+        output.push_str(&format!(
+            "        {:?} => Some({}),\n",
+            ext,
+            extension_info(ext)
+        ));
     }
 
-    output.push_str("pub fn get_extension_info(ext: &str) -> Option<ArchiveInfo> {");
+    output.push_str("        _ => EXTENSION_PATTERNS");
     output.push('\n');
-    output.push_str("    match ext.to_lowercase().as_str() {");
+    output.push_str("            .iter()");
     output.push('\n');
-
-    let mut sorted_exts: Vec<_> = ext_to_info.keys().collect();
-    sorted_exts.sort();
-
-    for ext in sorted_exts {
-        let (mime, desc) = &ext_to_info[ext];
-        // Ensure desc doesn't break string literal
-        output.push_str(&format!("        \"{}\" => Some(ArchiveInfo {{ category: \"{}\", description: \"{}\", hexdump: \"\" }}),\n", ext, mime, desc));
-    }
-
-    output.push_str("        _ => None,");
+    output.push_str(
+        "            .find(|(pattern, _)| crate::magic::extension_matches(pattern, &ext))",
+    );
+    output.push('\n');
+    output.push_str("            .map(|(_, info)| info.clone()),");
     output.push('\n');
     output.push_str("    }");
     output.push('\n');
@@ -267,72 +264,111 @@ fn main() {
     output.push('\n');
 
     struct SigMatch {
-        bytes: Vec<u8>,
-        offset: usize,
+        sig: schema::SigData,
         mime: String,
-        description: String,
-        hexdump_str: String,
+        ext: String,
+        /// Every extension filed with the same pattern, canonical one first.
+        extensions: Vec<String>,
     }
 
     let mut all_signatures: Vec<SigMatch> = Vec::new();
     for entry in &entries {
         for sig in &entry.signatures {
             all_signatures.push(SigMatch {
-                bytes: sig.bytes.clone(),
-                offset: sig.offset,
+                sig: sig.clone(),
                 mime: entry.category_mime.clone(),
-                description: sig.description.clone(),
-                hexdump_str: sig.hexdump_str.clone(),
+                ext: entry.ext.clone(),
+                extensions: Vec::new(),
             });
         }
     }
 
-    all_signatures.sort_by_key(|signature| std::cmp::Reverse(signature.bytes.len()));
+    for m in &mut all_signatures {
+        m.extensions = schema::owners(&entries, &m.sig);
+    }
+
+    // Fixed offsets before searched ranges, which can match almost anywhere
+    // in their window, then most significant bits first, so the first hit is
+    // the most specific one. Among equals, a shared pattern's canonical
+    // extension comes first.
+    all_signatures.sort_by_key(|m| {
+        let primary = PatternData {
+            bytes: m.sig.bytes.clone(),
+            mask: m.sig.mask.clone(),
+            offset: m.sig.offset,
+        };
+        let bits = significant_bits(&primary) + m.sig.and.iter().map(significant_bits).sum::<u32>();
+        let canonical = m.extensions[0].eq_ignore_ascii_case(&m.ext);
+        (m.sig.search > 0, std::cmp::Reverse(bits), !canonical)
+    });
 
-    output.push_str("pub fn check_magic_signature(data: &[u8]) -> Option<ArchiveInfo> {");
+    output.push_str("pub static SIGNATURES: &[MagicSignature] = &[");
     output.push('\n');
 
-    for sig in all_signatures {
+    for SigMatch {
+        sig,
+        mime,
+        ext,
+        extensions,
+    } in all_signatures
+    {
+        output.push_str("    MagicSignature {");
+        output.push('\n');
         output.push_str(&format!(
-            "    if data.len() >= {} && ",
-            sig.offset + sig.bytes.len()
+            "        format: Format::{},",
+            variant_name(&sig.format)
         ));
-
-        if sig.offset == 0 {
-            output.push_str("data.starts_with(&[");
-        } else {
-            output.push_str(&format!("data[{}..].starts_with(&[", sig.offset));
-        }
-
-        for (i, byte) in sig.bytes.iter().enumerate() {
-            if i > 0 {
-                output.push_str(", ");
-            }
-            output.push_str(&format!("0x{:02X}", byte));
+        output.push('\n');
+        output.push_str(&format!("        bytes: {},", byte_slice(&sig.bytes)));
+        output.push('\n');
+        output.push_str(&format!("        mask: {},", mask_literal(&sig.mask)));
+        output.push('\n');
+        output.push_str(&format!("        offset: {},", sig.offset));
+        output.push('\n');
+        output.push_str(&format!("        from_end: {},", sig.from_end));
+        output.push('\n');
+        output.push_str(&format!("        search: {},", sig.search));
+        output.push('\n');
+        output.push_str("        and: &[");
+        for pattern in &sig.and {
+            output.push_str(&format!(
+                "BytePattern {{ bytes: {}, mask: {}, offset: {} }}, ",
+                byte_slice(&pattern.bytes),
+                mask_literal(&pattern.mask),
+                pattern.offset
+            ));
         }
-        output.push_str("]){ ");
+        output.push_str("],");
         output.push('\n');
-        output.push_str("        return Some(ArchiveInfo {");
+        output.push_str(&format!(
+            "        extension_required: {},",
+            sig.extension_required
+        ));
         output.push('\n');
-        // Explicitly adding commas
-        output.push_str(&format!("            category: \"{}\",", sig.mime));
+        output.push_str(&format!("        category: {:?},", mime));
         output.push('\n');
         output.push_str(&format!(
-            "            description: \"{}\",",
-            sig.description
+            "        mime_type: {},",
+            mime_literal(&sig.format)
         ));
         output.push('\n');
-        output.push_str(&format!("            hexdump: \"{}\"", sig.hexdump_str));
+        output.push_str(&format!("        description: {:?},", sig.description));
         output.push('\n');
-        output.push_str("        });");
+        output.push_str(&format!("        hexdump: {:?},", sig.hexdump_str));
         output.push('\n');
-        output.push_str("    }");
+        output.push_str(&format!("        extension: {:?},", ext));
+        output.push('\n');
+        let extensions: Vec<String> = extensions.iter().map(|e| format!("{:?}", e)).collect();
+        output.push_str(&format!(
+            "        extensions: &[{}],",
+            extensions.join(", ")
+        ));
+        output.push('\n');
+        output.push_str("    },");
         output.push('\n');
     }
 
-    output.push_str("    None");
-    output.push('\n');
-    output.push('}');
+    output.push_str("];");
     output.push('\n');
 
     fs::write(&dest_path, output).unwrap();
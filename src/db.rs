@@ -0,0 +1,311 @@
+//! Signature databases that can be extended at runtime.
+//!
+//! The built-in database is compiled from `archive_signatures.yaml` by
+//! `build.rs`. [`SignatureDb`] starts from it (or from nothing) and merges in
+//! further files in the same schema, so that in-house formats can be detected
+//! without forking the crate.
+
+use crate::magic::{self, BytePattern, MagicSignature};
+use crate::{ArchiveInfo, CATEGORIES, Format, SignatureDbError, generated, schema};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::hash::Hash;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+
+/// The signatures and extensions a [`Detector`](crate::Detector) recognizes.
+///
+/// Loaded signatures are ranked together with the existing ones: fixed
+/// offsets before searched ranges, then most specific first. On a tie, the
+/// most recently loaded signature wins. Extensions loaded at runtime are
+/// looked up before the built-in ones.
+///
+/// Loaded data lives for the rest of the process so that results can keep
+/// [`ArchiveInfo`]'s `'static` fields. It is shared between loads, so
+/// reloading the same file does not use more memory, but every distinct
+/// signature ever loaded is kept.
+///
+/// # Example
+///
+/// ```
+/// use isarchive::{Detector, Format, SignatureDb};
+///
+/// let mut db = SignatureDb::builtin();
+/// db.load_str(
+///     r#"
+/// containers:
+///   .acme:
+///     mime_type: "application/x-acme"
+///     signatures:
+///       - description: "ACME asset bundle"
+///         hexdump: "41 43 4D 45 01"
+/// "#,
+/// )
+/// .unwrap();
+///
+/// let detector = Detector::new(db);
+/// let info = detector.analyze_bytes(b"ACME\x01...", None).unwrap();
+/// assert_eq!(info.format, Format::Custom);
+/// assert_eq!(info.extension, ".acme");
+/// assert_eq!(info.mime_type, Some("application/x-acme"));
+/// ```
+#[derive(Clone)]
+pub struct SignatureDb {
+    /// Most specific first.
+    signatures: Cow<'static, [&'static MagicSignature]>,
+    /// Extension keys loaded at runtime, most recently loaded first.
+    extensions: Vec<(&'static str, ArchiveInfo)>,
+    /// Whether the compiled-in extension table is consulted.
+    builtin: bool,
+}
+
+impl SignatureDb {
+    /// The database compiled into the crate, as used by [`analyze`](crate::analyze).
+    pub fn builtin() -> Self {
+        SignatureDb {
+            signatures: Cow::Borrowed(magic::builtin()),
+            extensions: Vec::new(),
+            builtin: true,
+        }
+    }
+
+    /// A database with no signatures or extensions, for detecting only the
+    /// formats loaded into it.
+    pub fn empty() -> Self {
+        SignatureDb {
+            signatures: Cow::Borrowed(&[]),
+            extensions: Vec::new(),
+            builtin: false,
+        }
+    }
+
+    /// Loads a YAML file in the schema of `archive_signatures.yaml` and
+    /// merges it into the database.
+    ///
+    /// Nothing is merged if the file is invalid.
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<(), SignatureDbError> {
+        let yaml = fs::read_to_string(path).map_err(SignatureDbError::Io)?;
+        self.load_str(&yaml)
+    }
+
+    /// Like [`load`](SignatureDb::load), for YAML already in memory.
+    pub fn load_str(&mut self, yaml: &str) -> Result<(), SignatureDbError> {
+        let entries = schema::parse(yaml).map_err(SignatureDbError::Invalid)?;
+        let mime_types = schema::mime_types(&entries).map_err(SignatureDbError::Invalid)?;
+        // A format without a `mime_type` in the file keeps the built-in one.
+        let mime_type = |id: &str, format: Format| match mime_types.get(id) {
+            Some(mime_type) => Some(mime_type.as_str()),
+            None => builtin_mime_type(format),
+        };
+        // A key without its own `format` keeps the format the built-in
+        // database gives it.
+        let key_format = |ext: &str, id: &str| match builtin_key(&ext.to_lowercase()) {
+            Some(info) if schema::key_format_id(ext).as_deref() == Some(id) => info.format,
+            _ => format(id),
+        };
+
+        let mut interner = Interner::lock();
+        let mut signatures: Vec<&'static MagicSignature> = Vec::new();
+        for entry in &entries {
+            for sig in &entry.signatures {
+                let and = sig
+                    .and
+                    .iter()
+                    .map(|pattern| BytePattern {
+                        bytes: interner.bytes(&pattern.bytes),
+                        mask: pattern.mask.as_deref().map(|mask| interner.bytes(mask)),
+                        offset: pattern.offset,
+                    })
+                    .collect();
+                let extensions = schema::owners(&entries, sig)
+                    .iter()
+                    .map(|ext| interner.str(ext))
+                    .collect();
+                let format = if sig.format == entry.format {
+                    key_format(&entry.ext, &entry.format)
+                } else {
+                    format(&sig.format)
+                };
+                let signature = MagicSignature {
+                    format,
+                    bytes: interner.bytes(&sig.bytes),
+                    mask: sig.mask.as_deref().map(|mask| interner.bytes(mask)),
+                    offset: sig.offset,
+                    from_end: sig.from_end,
+                    search: sig.search,
+                    and: interner.patterns(and),
+                    extension_required: sig.extension_required,
+                    category: category(&entry.category_mime),
+                    mime_type: mime_type(&sig.format, format).map(|mime| interner.str(mime)),
+                    description: interner.str(&sig.description),
+                    hexdump: interner.str(&sig.hexdump_str),
+                    extension: interner.str(&entry.ext),
+                    extensions: interner.list(extensions),
+                };
+                signatures.push(interner.signature(signature));
+            }
+        }
+
+        let mut extensions: Vec<(&'static str, ArchiveInfo)> = schema::extension_infos(&entries)
+            .into_iter()
+            .filter(|(ext, _)| !schema::needs_magic(ext))
+            .map(|(ext, key)| {
+                let ext = interner.str(&ext);
+                let format = key_format(ext, &key.format);
+                let info = ArchiveInfo {
+                    format,
+                    category: category(&key.category_mime),
+                    mime_type: mime_type(&key.format, format).map(|mime| interner.str(mime)),
+                    description: interner.str(&key.description),
+                    hexdump: "",
+                    extension: ext,
+                    extensions: interner.list(vec![ext]),
+                    confidence: magic::EXTENSION_CONFIDENCE,
+                    offset: None,
+                };
+                (ext, info)
+            })
+            .collect();
+
+        // Loaded signatures come first so that they win ties after the
+        // stable sort.
+        signatures.extend(self.signatures.iter());
+        signatures.sort_by_key(|sig| {
+            let canonical = sig.extensions[0].eq_ignore_ascii_case(sig.extension);
+            (sig.search > 0, Reverse(sig.significant_bits()), !canonical)
+        });
+        self.signatures = Cow::Owned(signatures);
+
+        extensions.append(&mut self.extensions);
+        self.extensions = extensions;
+        Ok(())
+    }
+
+    /// The database's signatures, most specific first.
+    pub fn signatures(&self) -> impl Iterator<Item = &'static MagicSignature> + '_ {
+        self.signatures.iter().copied()
+    }
+
+    pub(crate) fn signature_list(&self) -> &[&'static MagicSignature] {
+        &self.signatures
+    }
+
+    /// Looks up a lowercased extension suffix such as `.gz`, trying exact
+    /// keys before glob patterns and loaded keys before built-in ones.
+    pub(crate) fn extension(&self, suffix: &str) -> Option<ArchiveInfo> {
+        let exact = self
+            .extensions
+            .iter()
+            .find(|(key, _)| !schema::is_pattern(key) && *key == suffix);
+        let pattern = || {
+            self.extensions
+                .iter()
+                .find(|(key, _)| schema::is_pattern(key) && magic::extension_matches(key, suffix))
+        };
+        match exact.or_else(pattern) {
+            Some((_, info)) => Some(info.clone()),
+            None if self.builtin => generated::get_extension_info(suffix),
+            None => None,
+        }
+    }
+}
+
+impl Default for SignatureDb {
+    fn default() -> Self {
+        SignatureDb::builtin()
+    }
+}
+
+impl fmt::Debug for SignatureDb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignatureDb")
+            .field("signatures", &self.signatures.len())
+            .field("extensions", &self.extensions.len())
+            .field("builtin", &self.builtin)
+            .finish()
+    }
+}
+
+/// What the built-in database reports for a lowercased extension key.
+fn builtin_key(ext: &str) -> Option<ArchiveInfo> {
+    generated::get_extension_info(ext)
+}
+
+/// The media type the built-in database gives a format.
+fn builtin_mime_type(format: Format) -> Option<&'static str> {
+    if format == Format::Custom {
+        return None;
+    }
+    generated::FORMATS
+        .iter()
+        .find(|info| info.format == format)
+        .and_then(|info| info.mime_type)
+}
+
+/// The built-in format with this id, or [`Format::Custom`].
+fn format(id: &str) -> Format {
+    Format::from_id(id).unwrap_or(Format::Custom)
+}
+
+/// The static copy of a category the parser produced, which is always one of
+/// [`CATEGORIES`].
+fn category(category_mime: &str) -> &'static str {
+    CATEGORIES
+        .iter()
+        .find(|c| **c == category_mime)
+        .expect("the parser only files entries under known sections")
+}
+
+/// Everything loaded so far, kept for the rest of the process and shared
+/// between loads so that identical data is only stored once.
+#[derive(Default)]
+struct Interner {
+    strs: HashSet<&'static str>,
+    bytes: HashSet<&'static [u8]>,
+    lists: HashSet<&'static [&'static str]>,
+    patterns: HashSet<&'static [BytePattern]>,
+    signatures: HashSet<&'static MagicSignature>,
+}
+
+impl Interner {
+    fn lock() -> MutexGuard<'static, Interner> {
+        static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+        INTERNER
+            .get_or_init(Mutex::default)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn str(&mut self, s: &str) -> &'static str {
+        intern(&mut self.strs, s.into())
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> &'static [u8] {
+        intern(&mut self.bytes, bytes.into())
+    }
+
+    fn list(&mut self, list: Vec<&'static str>) -> &'static [&'static str] {
+        intern(&mut self.lists, list.into_boxed_slice())
+    }
+
+    fn patterns(&mut self, patterns: Vec<BytePattern>) -> &'static [BytePattern] {
+        intern(&mut self.patterns, patterns.into_boxed_slice())
+    }
+
+    fn signature(&mut self, signature: MagicSignature) -> &'static MagicSignature {
+        intern(&mut self.signatures, Box::new(signature))
+    }
+}
+
+/// The copy of `value` in `set`, adding it if there is none yet.
+fn intern<T: ?Sized + Eq + Hash>(set: &mut HashSet<&'static T>, value: Box<T>) -> &'static T {
+    if let Some(&interned) = set.get(&*value) {
+        return interned;
+    }
+    let leaked: &'static T = Box::leak(value);
+    set.insert(leaked);
+    leaked
+}
@@ -0,0 +1,305 @@
+//! Detection against a chosen signature database.
+//!
+//! The crate's free functions run a [`Detector`] over the built-in database;
+//! building one directly allows signatures loaded at runtime to be used.
+
+use crate::magic::{self, Sample};
+use crate::pool;
+use crate::{
+    ArchiveInfo, Candidate, DetectError, DetectOptions, Explanation, ExtensionCheck, SignatureDb,
+    SuffixLookup, Verdict,
+};
+use std::io::{Read, Seek};
+use std::path::Path;
+
+/// Detects archives using a [`SignatureDb`] and [`DetectOptions`].
+///
+/// Each method behaves like the free function of the same name, e.g.
+/// [`Detector::try_analyze`] like [`try_analyze`](crate::try_analyze), with
+/// this detector's database and options. A detector can be shared between
+/// threads.
+///
+/// # Example
+///
+/// ```no_run
+/// use isarchive::{DetectOptions, Detector, SignatureDb};
+///
+/// let mut db = SignatureDb::builtin();
+/// db.load("in-house-formats.yaml").expect("valid signature file");
+/// let detector = Detector::new(db).with_options(DetectOptions::magic_only());
+///
+/// if let Some(info) = detector.analyze("bundle.bin") {
+///     println!("{} ({})", info.description, info.category);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Detector {
+    db: SignatureDb,
+    options: DetectOptions,
+}
+
+impl Detector {
+    /// Creates a detector over `db` with the default options.
+    pub fn new(db: SignatureDb) -> Self {
+        Detector {
+            db,
+            options: DetectOptions::default(),
+        }
+    }
+
+    /// Uses only the detection methods enabled in `options`.
+    pub fn with_options(mut self, options: DetectOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// The signatures this detector matches against.
+    pub fn db(&self) -> &SignatureDb {
+        &self.db
+    }
+
+    /// The detection methods this detector runs.
+    pub fn options(&self) -> DetectOptions {
+        self.options
+    }
+
+    /// Analyzes the file, see [`analyze`](crate::analyze).
+    pub fn analyze<P: AsRef<Path>>(&self, path: P) -> Option<ArchiveInfo> {
+        let path = path.as_ref();
+        let name = magic::file_name(path);
+
+        // 1. Magic Number Check (Priority)
+        if self.options.magic
+            && let Ok(sample) = self.read_sample(path)
+            && let Some(info) = magic::check_sample_in(self.db.signature_list(), &sample, name)
+        {
+            return Some(info);
+        }
+
+        // 2. Extension Check (Fallback)
+        if !self.options.extension {
+            return None;
+        }
+        name.and_then(|name| self.extension_info(name))
+    }
+
+    /// Analyzes the file, reporting why it could not be read, see
+    /// [`try_analyze`](crate::try_analyze).
+    pub fn try_analyze<P: AsRef<Path>>(&self, path: P) -> Result<Option<ArchiveInfo>, DetectError> {
+        let path = path.as_ref();
+        let name = magic::file_name(path);
+        if !self.options.magic {
+            return Ok(name
+                .filter(|_| self.options.extension)
+                .and_then(|name| self.extension_info(name)));
+        }
+
+        let sample = self.read_sample(path)?;
+        self.detect(&sample, name)
+    }
+
+    /// Returns every matching signature, see [`analyze_all`](crate::analyze_all).
+    pub fn analyze_all<P: AsRef<Path>>(&self, path: P) -> Vec<ArchiveInfo> {
+        let path = path.as_ref();
+        let name = magic::file_name(path);
+
+        if self.options.magic
+            && let Ok(sample) = self.read_sample(path)
+        {
+            let candidates = magic::check_sample_all_in(self.db.signature_list(), &sample, name);
+            if !candidates.is_empty() {
+                return candidates;
+            }
+        }
+
+        name.filter(|_| self.options.extension)
+            .and_then(|name| self.extension_info(name))
+            .into_iter()
+            .collect()
+    }
+
+    /// Analyzes many files concurrently, see
+    /// [`analyze_many`](crate::analyze_many).
+    pub fn analyze_many<I>(
+        &self,
+        paths: I,
+        jobs: usize,
+    ) -> Vec<Result<Option<ArchiveInfo>, DetectError>>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path> + Sync,
+    {
+        let paths: Vec<I::Item> = paths.into_iter().collect();
+        let mut results = Vec::with_capacity(paths.len());
+        pool::run_pool(
+            &paths,
+            jobs,
+            |path| self.try_analyze(path),
+            |_, result| results.push(result),
+        );
+        results
+    }
+
+    /// Analyzes an in-memory buffer, see [`analyze_bytes`](crate::analyze_bytes).
+    pub fn analyze_bytes(&self, data: &[u8], filename: Option<&str>) -> Option<ArchiveInfo> {
+        self.detect(&Sample::from_bytes(data), filename)
+            .ok()
+            .flatten()
+    }
+
+    /// Analyzes a seekable stream, see [`analyze_reader`](crate::analyze_reader).
+    pub fn analyze_reader<R: Read + Seek>(
+        &self,
+        mut reader: R,
+        filename: Option<&str>,
+    ) -> Result<Option<ArchiveInfo>, DetectError> {
+        let sample =
+            Sample::read_for(&mut reader, self.db.signature_list()).map_err(DetectError::Io)?;
+        self.detect(&sample, filename)
+    }
+
+    /// Analyzes a stream that cannot seek, see
+    /// [`analyze_stream`](crate::analyze_stream).
+    pub fn analyze_stream<R: Read>(
+        &self,
+        mut reader: R,
+        filename: Option<&str>,
+    ) -> Result<Option<ArchiveInfo>, DetectError> {
+        let sample = Sample::read_stream_for(&mut reader, self.db.signature_list())
+            .map_err(DetectError::Io)?;
+        self.detect(&sample, filename)
+    }
+
+    /// Compares the file's content with its name, see
+    /// [`check_extension`](crate::check_extension). Both methods always run,
+    /// whatever the detector's options.
+    pub fn check_extension<P: AsRef<Path>>(&self, path: P) -> Result<ExtensionCheck, DetectError> {
+        let path = path.as_ref();
+        let sample = self.read_sample(path)?;
+        let name = magic::file_name(path);
+
+        let candidates = magic::check_sample_all_in(self.db.signature_list(), &sample, name);
+        let by_name = name.and_then(|name| self.extension_info(name));
+
+        let check = match (name, by_name) {
+            (Some(name), Some(extension)) => {
+                let agreeing = candidates.iter().find(|candidate| {
+                    candidate
+                        .extensions
+                        .iter()
+                        .any(|ext| magic::has_extension(name, ext))
+                });
+                match (agreeing, candidates.first()) {
+                    (Some(magic), _) => ExtensionCheck::Match {
+                        magic: magic.clone(),
+                        extension,
+                    },
+                    (None, Some(magic)) => ExtensionCheck::Mismatch {
+                        magic: magic.clone(),
+                        extension,
+                    },
+                    (None, None) => ExtensionCheck::ExtensionOnly(extension),
+                }
+            }
+            _ => match candidates.into_iter().next() {
+                Some(magic) => ExtensionCheck::MagicOnly(magic),
+                None => ExtensionCheck::Neither,
+            },
+        };
+        Ok(check)
+    }
+
+    /// Reports how the file would be analyzed, see [`explain`](crate::explain).
+    pub fn explain<P: AsRef<Path>>(&self, path: P) -> Result<Explanation, DetectError> {
+        let path = path.as_ref();
+        let name = magic::file_name(path);
+
+        let mut candidates = Vec::new();
+        if self.options.magic {
+            let sample = self.read_sample(path)?;
+            let mut selected = false;
+            for (info, admitted) in magic::trace_sample_in(self.db.signature_list(), &sample, name)
+            {
+                let verdict = if !admitted {
+                    Verdict::MissingExtension
+                } else if selected {
+                    Verdict::Outranked
+                } else {
+                    selected = true;
+                    Verdict::Selected
+                };
+                candidates.push(Candidate { info, verdict });
+            }
+        }
+
+        let mut result = candidates
+            .iter()
+            .find(|c| c.verdict == Verdict::Selected)
+            .map(|c| c.info.clone());
+
+        let mut suffixes = Vec::new();
+        if result.is_none() && self.options.extension {
+            for suffix in name.map(extension_suffixes).unwrap_or_default() {
+                let info = self.db.extension(&suffix);
+                let found = info.is_some();
+                if found {
+                    result = info.clone();
+                }
+                suffixes.push(SuffixLookup { suffix, info });
+                if found {
+                    break;
+                }
+            }
+        }
+
+        Ok(Explanation {
+            result,
+            candidates,
+            suffixes,
+        })
+    }
+
+    fn read_sample(&self, path: &Path) -> Result<Sample<'static>, DetectError> {
+        magic::read_sample_in(self.db.signature_list(), path)
+    }
+
+    fn detect(
+        &self,
+        sample: &Sample,
+        name: Option<&str>,
+    ) -> Result<Option<ArchiveInfo>, DetectError> {
+        let signatures = self.db.signature_list();
+        if self.options.magic
+            && let Some(info) = magic::check_sample_in(signatures, sample, name)
+        {
+            return Ok(Some(info));
+        }
+
+        if self.options.extension
+            && let Some(info) = name.and_then(|name| self.extension_info(name))
+        {
+            return Ok(Some(info));
+        }
+
+        if self.options.magic && sample.len() < magic::min_signature_len_in(signatures) as u64 {
+            return Err(DetectError::TooShort(sample.len()));
+        }
+
+        Ok(None)
+    }
+
+    pub(crate) fn extension_info(&self, name: &str) -> Option<ArchiveInfo> {
+        extension_suffixes(name)
+            .iter()
+            .find_map(|suffix| self.db.extension(suffix))
+    }
+}
+
+/// Lowercased dot-suffixes of `name`, longest first.
+fn extension_suffixes(name: &str) -> Vec<String> {
+    let name_lower = name.to_lowercase();
+    name_lower
+        .match_indices('.')
+        .map(|(i, _)| name_lower[i..].to_string())
+        .collect()
+}
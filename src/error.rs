@@ -0,0 +1,65 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Errors that prevent a file from being analyzed.
+#[derive(Debug)]
+pub enum DetectError {
+    /// The file could not be opened, e.g. permission denied or a directory.
+    Unreadable(io::Error),
+    /// Reading the file's contents failed after it was opened.
+    Io(io::Error),
+    /// The file holds fewer bytes than the shortest known signature and its
+    /// name has no archive extension, so nothing can be said about it.
+    TooShort(u64),
+}
+
+impl fmt::Display for DetectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DetectError::Unreadable(err) => write!(f, "cannot open ({})", err),
+            DetectError::Io(err) => write!(f, "read error ({})", err),
+            DetectError::TooShort(len) => {
+                write!(f, "too short to identify ({} bytes)", len)
+            }
+        }
+    }
+}
+
+impl Error for DetectError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DetectError::Unreadable(err) | DetectError::Io(err) => Some(err),
+            DetectError::TooShort(_) => None,
+        }
+    }
+}
+
+/// Errors that prevent a signature database from being loaded, see
+/// [`SignatureDb::load`](crate::SignatureDb::load).
+#[derive(Debug)]
+pub enum SignatureDbError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The contents do not follow the `archive_signatures.yaml` schema. The
+    /// message names the offending line.
+    Invalid(String),
+}
+
+impl fmt::Display for SignatureDbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureDbError::Io(err) => write!(f, "cannot read ({})", err),
+            SignatureDbError::Invalid(message) => write!(f, "invalid signatures ({})", message),
+        }
+    }
+}
+
+impl Error for SignatureDbError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SignatureDbError::Io(err) => Some(err),
+            SignatureDbError::Invalid(_) => None,
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! A step-by-step account of how a detection result was reached.
+//!
+//! Used by the CLI's `--verbose` mode to show which signature fired, which
+//! other signatures matched but lost, and which extension suffixes were
+//! looked up, without reading the generated signature table.
+
+use crate::ArchiveInfo;
+
+/// Everything [`explain`](crate::explain) looked at while analyzing a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    /// The result the same detection options produce.
+    pub result: Option<ArchiveInfo>,
+    /// Every signature whose bytes matched, most specific first.
+    pub candidates: Vec<Candidate>,
+    /// Extension suffixes looked up, longest first, and what each one
+    /// matched. Empty when a magic number matched or extensions are
+    /// disabled, since the lookup never ran.
+    pub suffixes: Vec<SuffixLookup>,
+}
+
+/// A signature whose bytes matched the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// The signature's details, including its bytes, offset, category and
+    /// extension key.
+    pub info: ArchiveInfo,
+    pub verdict: Verdict,
+}
+
+/// What became of a matching signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Reported as the result.
+    Selected,
+    /// Matched, but a more specific signature was reported.
+    Outranked,
+    /// Matched, but the signature is only trusted alongside its extension,
+    /// which the file name lacks.
+    MissingExtension,
+}
+
+/// One step of the longest-suffix-first extension lookup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuffixLookup {
+    /// The lowercased suffix, e.g. `.tar.gz`.
+    pub suffix: String,
+    /// The extension entry it matched, if any.
+    pub info: Option<ArchiveInfo>,
+}
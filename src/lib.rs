@@ -1,13 +1,169 @@
+use std::io::{Read, Seek};
 use std::path::Path;
 
+mod db;
+mod detector;
+mod error;
+mod explain;
 pub mod magic;
+mod pool;
+mod schema;
+#[cfg(feature = "serde")]
+mod ser;
+mod structure;
 
 // Encapsulate generated code
 pub(crate) mod generated {
     include!(concat!(env!("OUT_DIR"), "/extensions.rs"));
 }
 
-pub use generated::ArchiveInfo;
+pub use db::SignatureDb;
+pub use detector::Detector;
+pub use error::{DetectError, SignatureDbError};
+pub use explain::{Candidate, Explanation, SuffixLookup, Verdict};
+pub use generated::{CATEGORIES, Format};
+
+/// Details about a detected archive format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveInfo {
+    /// The identified format. Use [`Format::id`] for a stable string form.
+    pub format: Format,
+    /// MIME-like category derived from the YAML section, e.g. `archive/storage`.
+    pub category: &'static str,
+    /// IANA media type of the format, e.g. `application/zip`, or `None` for
+    /// formats without a registered or widely used type.
+    pub mime_type: Option<&'static str>,
+    /// Human readable description of the format.
+    pub description: &'static str,
+    /// The magic bytes that matched, or an empty string for extension matches.
+    pub hexdump: &'static str,
+    /// The `archive_signatures.yaml` extension key this result came from, e.g. `.jar`.
+    pub extension: &'static str,
+    /// Every extension the format is known by, canonical one first, e.g.
+    /// `.zip`, `.aar`, `.apk`, ... for a ZIP header. Extension matches list
+    /// just the matched extension.
+    pub extensions: &'static [&'static str],
+    /// How much the result can be trusted, from `0.0` to `1.0`.
+    ///
+    /// Magic matches score higher for longer signatures, non-zero offsets,
+    /// a filename extension that agrees, and a passing structural check of
+    /// the header. Extension-only matches always score low.
+    pub confidence: f32,
+    /// Byte offset at which the signature matched, or `None` for extension
+    /// matches. For signatures with an `offset_range` this is where the
+    /// marker was actually found.
+    pub offset: Option<u64>,
+}
+
+impl ArchiveInfo {
+    /// Which detection method produced this result.
+    pub fn method(&self) -> Method {
+        if self.offset.is_some() {
+            Method::Magic
+        } else {
+            Method::Extension
+        }
+    }
+
+    /// The extension files of this format normally use, e.g. `.zip` for a
+    /// ZIP header regardless of the file's actual name.
+    pub fn canonical_extension(&self) -> &'static str {
+        self.extensions.first().copied().unwrap_or(self.extension)
+    }
+}
+
+/// One format the signature database knows about, as listed by
+/// [`supported_formats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatInfo {
+    pub format: Format,
+    /// The extension key from `archive_signatures.yaml` the format is listed
+    /// under, lowercased: the one named after the format where there is one.
+    /// May be a glob pattern such as `.paq#*`.
+    pub extension: &'static str,
+    /// Every extension key that recognizes the format by name, `extension`
+    /// first, e.g. `.tar.gz` and `.tgz`. Empty for formats only detected by
+    /// their content.
+    pub extensions: &'static [&'static str],
+    pub category: &'static str,
+    pub mime_type: Option<&'static str>,
+    pub description: &'static str,
+    /// Number of magic signatures filed under the extension. Zero for formats
+    /// recognized by extension alone.
+    pub signatures: usize,
+}
+
+/// Returns every format in the signature database, ordered by extension.
+///
+/// # Example
+///
+/// ```
+/// use isarchive::{Format, supported_formats};
+///
+/// let zip = supported_formats().find(|f| f.format == Format::Zip).unwrap();
+/// assert_eq!(zip.extension, ".zip");
+/// assert!(zip.signatures > 0);
+/// ```
+pub fn supported_formats() -> impl Iterator<Item = &'static FormatInfo> {
+    generated::FORMATS.iter()
+}
+
+/// How a result was detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// A magic number matched the file's content.
+    Magic,
+    /// Only the filename extension was recognized.
+    Extension,
+}
+
+impl Method {
+    /// Lowercase name of the method, `"magic"` or `"extension"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Method::Magic => "magic",
+            Method::Extension => "extension",
+        }
+    }
+}
+
+/// Which detection methods to run, see [`analyze_with`].
+///
+/// The default checks magic numbers first and falls back to the extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectOptions {
+    /// Match magic numbers against the file's content.
+    pub magic: bool,
+    /// Recognize the filename extension when no magic number matches.
+    pub extension: bool,
+}
+
+impl Default for DetectOptions {
+    fn default() -> Self {
+        DetectOptions {
+            magic: true,
+            extension: true,
+        }
+    }
+}
+
+impl DetectOptions {
+    /// Trusts only the content, so an empty `foo.zip` is not an archive.
+    pub fn magic_only() -> Self {
+        DetectOptions {
+            magic: true,
+            extension: false,
+        }
+    }
+
+    /// Looks only at the filename, without reading the file.
+    pub fn extension_only() -> Self {
+        DetectOptions {
+            magic: false,
+            extension: true,
+        }
+    }
+}
 
 /// Analyzes the file and returns detailed archive info.
 ///
@@ -29,28 +185,244 @@ pub use generated::ArchiveInfo;
 /// }
 /// ```
 pub fn analyze<P: AsRef<Path>>(path: P) -> Option<ArchiveInfo> {
-    let path = path.as_ref();
+    analyze_with(path, DetectOptions::default())
+}
 
-    // 1. Magic Number Check (Priority)
-    if let Some(info) = magic::check_magic(path) {
-        return Some(info);
-    }
+/// Analyzes the file using only the detection methods enabled in `options`.
+///
+/// # Example
+///
+/// ```no_run
+/// use isarchive::{DetectOptions, analyze_with};
+///
+/// // An empty or renamed file is not trusted just because of its name.
+/// let info = analyze_with("upload.zip", DetectOptions::magic_only());
+/// ```
+pub fn analyze_with<P: AsRef<Path>>(path: P, options: DetectOptions) -> Option<ArchiveInfo> {
+    Detector::default().with_options(options).analyze(path)
+}
 
-    // 2. Extension Check (Fallback)
-    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-        let name_lower = name.to_lowercase();
-        let indices: Vec<_> = name_lower.match_indices('.').map(|(i, _)| i).collect();
+/// Analyzes the file, reporting why it could not be read instead of
+/// returning `None`.
+///
+/// Returns `Ok(None)` only when the file was read and is not an archive. A
+/// file too short to hold any signature and without an archive extension
+/// yields [`DetectError::TooShort`].
+///
+/// # Example
+///
+/// ```no_run
+/// use isarchive::try_analyze;
+///
+/// match try_analyze("upload.bin") {
+///     Ok(Some(info)) => println!("Archive: {}", info.description),
+///     Ok(None) => println!("Not an archive"),
+///     Err(err) => eprintln!("Cannot analyze: {}", err),
+/// }
+/// ```
+pub fn try_analyze<P: AsRef<Path>>(path: P) -> Result<Option<ArchiveInfo>, DetectError> {
+    try_analyze_with(path, DetectOptions::default())
+}
 
-        // Try longest suffix first
-        for i in indices {
-            let suffix = &name_lower[i..];
-            if let Some(info) = generated::get_extension_info(suffix) {
-                return Some(info);
-            }
-        }
-    }
+/// Like [`try_analyze`], using only the detection methods enabled in
+/// `options`.
+///
+/// With [`DetectOptions::extension_only`] the file is never opened, so no
+/// error is returned.
+pub fn try_analyze_with<P: AsRef<Path>>(
+    path: P,
+    options: DetectOptions,
+) -> Result<Option<ArchiveInfo>, DetectError> {
+    Detector::default().with_options(options).try_analyze(path)
+}
+
+/// Analyzes an in-memory buffer, such as an uploaded file.
+///
+/// Runs the same magic-then-extension logic as [`analyze`] without touching
+/// the filesystem. `filename` is only used for the extension fallback and
+/// confidence scoring.
+///
+/// # Example
+///
+/// ```
+/// use isarchive::analyze_bytes;
+///
+/// let upload = [0x50, 0x4B, 0x03, 0x04, 0x14, 0x00];
+/// let info = analyze_bytes(&upload, Some("report.zip")).unwrap();
+/// assert_eq!(info.category, "archive/compressed-archive");
+/// ```
+pub fn analyze_bytes(data: &[u8], filename: Option<&str>) -> Option<ArchiveInfo> {
+    Detector::default().analyze_bytes(data, filename)
+}
+
+/// Analyzes a seekable stream from its beginning.
+///
+/// The reader is rewound to the start before reading, and errors are
+/// reported the same way as [`try_analyze`]. `filename` is only used for the
+/// extension fallback and confidence scoring.
+///
+/// # Example
+///
+/// ```
+/// use isarchive::analyze_reader;
+/// use std::io::Cursor;
+///
+/// let stream = Cursor::new(vec![0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C, 0x00, 0x04]);
+/// let info = analyze_reader(stream, None).unwrap().unwrap();
+/// assert!(info.description.contains("7-Zip"));
+/// ```
+pub fn analyze_reader<R: Read + Seek>(
+    reader: R,
+    filename: Option<&str>,
+) -> Result<Option<ArchiveInfo>, DetectError> {
+    Detector::default().analyze_reader(reader, filename)
+}
+
+/// Analyzes a stream that cannot seek, such as standard input or a socket.
+///
+/// The stream is read to its end so that end-anchored signatures can be
+/// checked, but only the leading and trailing windows signatures look at are
+/// kept in memory. `filename` is only used for the extension fallback and
+/// confidence scoring.
+///
+/// # Example
+///
+/// ```
+/// use isarchive::analyze_stream;
+///
+/// let pipe: &[u8] = &[0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00];
+/// let info = analyze_stream(pipe, None).unwrap().unwrap();
+/// assert_eq!(info.extension, ".gz");
+/// ```
+pub fn analyze_stream<R: Read>(
+    reader: R,
+    filename: Option<&str>,
+) -> Result<Option<ArchiveInfo>, DetectError> {
+    Detector::default().analyze_stream(reader, filename)
+}
+
+/// Analyzes the file and returns every matching archive signature.
+///
+/// Unlike [`analyze`], which stops at the first hit, this reports each
+/// candidate whose magic number matches, ordered from most to least specific.
+/// A ZIP file, for instance, yields one entry per extension that uses the ZIP
+/// signature (`.zip`, `.jar`, `.apk`, ...), letting callers resolve ambiguous
+/// formats themselves. The first element is always the result of [`analyze`].
+///
+/// If no signature matches, the extension fallback result is returned alone.
+///
+/// # Example
+///
+/// ```no_run
+/// use isarchive::analyze_all;
+///
+/// for info in analyze_all("bundle.apk") {
+///     println!("{} ({}, {})", info.description, info.extension, info.category);
+/// }
+/// ```
+pub fn analyze_all<P: AsRef<Path>>(path: P) -> Vec<ArchiveInfo> {
+    Detector::default().analyze_all(path)
+}
+
+/// Analyzes many files concurrently with [`try_analyze`], returning the
+/// results in the same order as `paths`.
+///
+/// Up to `jobs` standard library threads do the work; `0` uses one per
+/// available CPU and `1` analyzes the files sequentially on the calling
+/// thread.
+///
+/// # Example
+///
+/// ```no_run
+/// use isarchive::analyze_many;
+///
+/// let paths = ["a.zip", "b.bin", "c.tar.gz"];
+/// for (path, result) in paths.iter().zip(analyze_many(&paths, 4)) {
+///     match result {
+///         Ok(Some(info)) => println!("{}: {}", path, info.description),
+///         Ok(None) => println!("{}: not an archive", path),
+///         Err(err) => println!("{}: {}", path, err),
+///     }
+/// }
+/// ```
+pub fn analyze_many<I>(paths: I, jobs: usize) -> Vec<Result<Option<ArchiveInfo>, DetectError>>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path> + Sync,
+{
+    Detector::default().analyze_many(paths, jobs)
+}
+
+/// Outcome of comparing a file's content with its name, see
+/// [`check_extension`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExtensionCheck {
+    /// The magic number and the extension identify the same format.
+    Match {
+        magic: ArchiveInfo,
+        extension: ArchiveInfo,
+    },
+    /// The magic number identifies a format the extension does not belong to,
+    /// such as a plain tar named `.tar.gz`.
+    Mismatch {
+        magic: ArchiveInfo,
+        extension: ArchiveInfo,
+    },
+    /// Only the magic number matched; the name has no archive extension.
+    MagicOnly(ArchiveInfo),
+    /// Only the extension matched; the content has no known signature.
+    ExtensionOnly(ArchiveInfo),
+    /// Neither method recognized the file.
+    Neither,
+}
+
+/// Runs the magic and extension checks independently and reports whether
+/// they agree.
+///
+/// The two agree when any matching signature is filed under one of the
+/// name's extensions, so a gzip stream named `.tar.gz` matches through its
+/// `.gz` suffix and a ZIP named `.jar` matches through the shared ZIP header.
+///
+/// # Example
+///
+/// ```no_run
+/// use isarchive::{ExtensionCheck, check_extension};
+///
+/// match check_extension("invoice.pdf.zip") {
+///     Ok(ExtensionCheck::Match { .. }) => println!("ok"),
+///     Ok(ExtensionCheck::Mismatch { magic, .. }) => {
+///         println!("really a {}", magic.description)
+///     }
+///     Ok(other) => println!("inconclusive: {:?}", other),
+///     Err(err) => println!("error: {}", err),
+/// }
+/// ```
+pub fn check_extension<P: AsRef<Path>>(path: P) -> Result<ExtensionCheck, DetectError> {
+    Detector::default().check_extension(path)
+}
 
-    None
+/// Reports how the file would be analyzed with `options`: every signature
+/// that matched and why all but one were passed over, and each extension
+/// suffix looked up.
+///
+/// # Example
+///
+/// ```no_run
+/// use isarchive::{DetectOptions, explain};
+///
+/// let trace = explain("backup.tar.gz", DetectOptions::default()).unwrap();
+/// for candidate in &trace.candidates {
+///     println!("{:?}: {}", candidate.verdict, candidate.info.description);
+/// }
+/// for lookup in &trace.suffixes {
+///     println!("{} -> {:?}", lookup.suffix, lookup.info.as_ref().map(|i| i.extension));
+/// }
+/// ```
+pub fn explain<P: AsRef<Path>>(
+    path: P,
+    options: DetectOptions,
+) -> Result<Explanation, DetectError> {
+    Detector::default().with_options(options).explain(path)
 }
 
 #[cfg(test)]
@@ -59,10 +431,14 @@ mod tests {
     use std::fs::File;
     use std::io::Write;
 
+    fn extension_info(name: &str) -> Option<ArchiveInfo> {
+        Detector::default().extension_info(name)
+    }
+
     #[test]
     fn test_magic_signature_zip() {
         let zip_magic = [0x50, 0x4B, 0x03, 0x04];
-        let info = generated::check_magic_signature(&zip_magic);
+        let info = magic::check_magic_signature(&zip_magic, None);
         assert!(info.is_some(), "ZIP magic should be detected");
         let info = info.unwrap();
         // The category should be one of the known archive categories
@@ -87,6 +463,48 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_extension_patterns() {
+        let squeezed = analyze_bytes(&[0x76, 0xFF, 0x00, 0x00], Some("README.TQT")).unwrap();
+        assert_eq!(squeezed.extension, ".?Q?");
+        assert_eq!(squeezed.description, "SQ squeezed data");
+        let szdd = b"SZDD\x88\xF0\x27\x33A\x00\x10\x00\x00\x00";
+        assert_eq!(
+            analyze_bytes(szdd, Some("setup.ex_")).unwrap().format,
+            Format::MsCompress
+        );
+        let crunched = analyze_bytes(&[0x76, 0xFE, 0x00, 0x00], Some("crunched.czm")).unwrap();
+        assert_eq!(crunched.format, Format::Crunched);
+
+        // Keys with fewer than three literal characters need their magic.
+        assert!(extension_info("README.TQT").is_none());
+        for name in ["schema.sql", "book.azw", "main.bzl", "drawing.dxf"] {
+            assert!(
+                analyze_bytes(b"plain text file", Some(name)).is_none(),
+                "{}",
+                name
+            );
+        }
+        assert!(analyze_bytes(&[0x76, 0xFF, 0x00, 0x00], Some("schema.bin")).is_none());
+
+        assert_eq!(extension_info("data.paq8px").unwrap().extension, ".paq#*");
+        assert_eq!(extension_info("data.lpaq1").unwrap().extension, ".lpaq#*");
+
+        // Exact keys win over patterns, and wildcards stay within one extension.
+        assert_eq!(extension_info("data.paq8").unwrap().extension, ".paq8");
+        assert!(extension_info("data.paqx").is_none());
+        assert!(extension_info("data.paq8.bak").is_none());
+        assert!(extension_info("data.?q?x").is_none());
+    }
+
+    #[test]
+    fn test_extension_without_signatures() {
+        let info = extension_info("backup.tar.gz").unwrap();
+        assert_eq!(info.extension, ".tar.gz");
+        assert_eq!(info.category, "archive/compressed-archive");
+        assert!(extension_info("page.html.br").is_some());
+    }
+
     #[test]
     fn test_extension_nonexistent() {
         let info = generated::get_extension_info(".nonexistent_extension_xyz");
@@ -129,4 +547,880 @@ mod tests {
         );
         assert!(result.unwrap().category.contains("archive"));
     }
+
+    #[test]
+    fn test_magic_signatures_all_candidates() {
+        let zip_magic = [0x50, 0x4B, 0x03, 0x04];
+        let candidates = magic::check_magic_signatures(&zip_magic, None);
+        let extensions: Vec<_> = candidates.iter().map(|info| info.extension).collect();
+        for ext in [".zip", ".jar", ".apk", ".appx"] {
+            assert!(extensions.contains(&ext), "ZIP magic should match {}", ext);
+        }
+        assert_eq!(
+            candidates.first(),
+            magic::check_magic_signature(&zip_magic, None).as_ref(),
+            "First candidate should be the single-match result"
+        );
+    }
+
+    #[test]
+    fn test_analyze_all_extension_fallback() {
+        let filename = "test_analyze_all_fallback.zip";
+        {
+            let _file = File::create(filename).unwrap();
+        }
+
+        let result = analyze_all(filename);
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(
+            result.len(),
+            1,
+            "Only the extension match should be returned"
+        );
+        assert_eq!(result[0].extension, ".zip");
+    }
+
+    #[test]
+    fn test_confidence_scoring() {
+        let gzip = [0x1F, 0x8B, 0x08, 0x00, 0, 0, 0, 0, 0x00, 0x03];
+        let named = magic::check_magic_signature(&gzip, Some("data.gz")).unwrap();
+        let unnamed = magic::check_magic_signature(&gzip, None).unwrap();
+        assert!(
+            named.confidence > unnamed.confidence,
+            "Matching extension should raise confidence"
+        );
+
+        let mut corrupt = gzip;
+        corrupt[3] = 0xFF;
+        let corrupt = magic::check_magic_signature(&corrupt, None).unwrap();
+        assert!(
+            corrupt.confidence < unnamed.confidence,
+            "Failed structural check should lower confidence"
+        );
+
+        let freeze = magic::check_magic_signature(&[0x1F, 0x9E], None).unwrap();
+        let coff = magic::check_magic_signature(b"!<arch>\n/       ", None).unwrap();
+        assert!(
+            coff.confidence > freeze.confidence,
+            "Longer signatures should score higher"
+        );
+        assert!((0.0..=1.0).contains(&coff.confidence));
+    }
+
+    #[test]
+    fn test_try_analyze_errors() {
+        let missing = try_analyze("test_try_analyze_missing.dat");
+        assert!(matches!(missing, Err(DetectError::Unreadable(_))));
+
+        let directory = try_analyze("src");
+        assert!(matches!(directory, Err(DetectError::Unreadable(_))));
+
+        let filename = "test_try_analyze_empty.dat";
+        {
+            let _file = File::create(filename).unwrap();
+        }
+        let empty = try_analyze(filename);
+        std::fs::remove_file(filename).unwrap();
+        assert!(matches!(empty, Err(DetectError::TooShort(0))));
+    }
+
+    #[test]
+    fn test_try_analyze_not_archive() {
+        let filename = "test_try_analyze_text.dat";
+        {
+            let mut file = File::create(filename).unwrap();
+            file.write_all(b"just some plain text").unwrap();
+        }
+        let result = try_analyze(filename);
+        std::fs::remove_file(filename).unwrap();
+        assert!(matches!(result, Ok(None)));
+    }
+
+    #[test]
+    fn test_analyze_bytes() {
+        let info = analyze_bytes(&[0x50, 0x4B, 0x03, 0x04], None);
+        assert!(info.is_some(), "ZIP magic should be detected in memory");
+
+        let info = analyze_bytes(&[], Some("upload.zip"));
+        assert_eq!(info.map(|i| i.extension), Some(".zip"));
+
+        assert!(analyze_bytes(b"plain text", Some("notes.txt")).is_none());
+    }
+
+    #[test]
+    fn test_analyze_reader_rewinds() {
+        use std::io::{Cursor, SeekFrom};
+
+        let mut stream = Cursor::new(vec![0x28, 0xB5, 0x2F, 0xFD, 0x00]);
+        stream.seek(SeekFrom::End(0)).unwrap();
+        let info = analyze_reader(&mut stream, None).unwrap();
+        assert!(info.is_some(), "Reader should be analyzed from the start");
+        assert_eq!(info.unwrap().category, "archive/stream-compression");
+    }
+
+    #[test]
+    fn test_masked_and_wildcard_signatures() {
+        let skippable = magic::check_magic_signature(&[0x53, 0x2A, 0x4D, 0x18], None);
+        assert_eq!(
+            skippable.map(|i| i.description),
+            Some("Zstandard skippable frame")
+        );
+        let outside_mask = magic::check_magic_signature(&[0x63, 0x2A, 0x4D, 0x18], None);
+        assert!(outside_mask.is_none(), "Masked bits must still match");
+
+        let lz4 = magic::check_magic_signature(b"pbz4", None).unwrap();
+        assert_eq!(lz4.description, "(LZ4) Apple Archive");
+        let unknown = magic::check_magic_signature(b"pbzq", None).unwrap();
+        assert_eq!(unknown.description, "( ) Apple Archive generic");
+        assert!(
+            magic::check_magic_signature(b"pbz", None).is_none(),
+            "Wildcard bytes must still be present"
+        );
+    }
+
+    #[test]
+    fn test_offset_range_reports_match_offset() {
+        let mut sfx = vec![0x4Du8; 5000];
+        sfx.extend_from_slice(&[0x50, 0x4B, 0x03, 0x04, 0x14, 0x00]);
+        let info = analyze_bytes(&sfx, None).unwrap();
+        assert_eq!(info.description, "- ZIP Self Extracting Archive");
+        assert_eq!(info.offset, Some(5000));
+
+        let zip = analyze_bytes(&[0x50, 0x4B, 0x03, 0x04], None).unwrap();
+        assert_eq!(zip.offset, Some(0), "Fixed offsets win over ranges");
+        assert_eq!(zip.description, "ZIP compressed archive");
+
+        // A ranged signature never outranks a fixed-offset one, however
+        // many more bits it compares.
+        let mut bzip2 = b"BZh91AY&SY".to_vec();
+        bzip2.resize(3000, 0x55);
+        bzip2.extend_from_slice(&[0x50, 0x4B, 0x03, 0x04]);
+        assert_eq!(analyze_bytes(&bzip2, None).unwrap().format, Format::Bz2);
+        let mut floppy = vec![0xEB, 0x3C, 0x90];
+        floppy.resize(2000, 0x00);
+        floppy.extend_from_slice(&[0x50, 0x4B, 0x03, 0x04, 0x14, 0x00]);
+        assert_eq!(
+            analyze_bytes(&floppy, None).unwrap().format,
+            Format::DmfImage
+        );
+
+        let mut script = b"echo unpacking\n# Generated by a tool\n".to_vec();
+        script.extend_from_slice(b"# This is a shell archive (produced by GNU sharutils)\n");
+        let info = analyze_bytes(&script, Some("bundle.shar")).unwrap();
+        assert!(info.description.contains("preamble"));
+        assert_eq!(info.offset, Some(37));
+        // A shebang matches the fixed-offset shar signature first.
+        let info = analyze_bytes(&[b"#!/bin/sh\n", &script[..]].concat(), None).unwrap();
+        assert_eq!(info.offset, Some(0));
+
+        let ext = analyze_bytes(&[], Some("empty.zip")).unwrap();
+        assert_eq!(ext.offset, None);
+    }
+
+    #[test]
+    fn test_format_identity() {
+        let seven = analyze_bytes(&[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C, 0x00, 0x04], None).unwrap();
+        assert_eq!(seven.format, Format::SevenZip);
+        assert_eq!(seven.format.id(), "seven_zip");
+
+        let rar5 = analyze_bytes(b"Rar!\x1A\x07\x01\x00", None).unwrap();
+        assert_eq!(rar5.format, Format::Rar5);
+        let rar4 = analyze_bytes(b"Rar!\x1A\x07\x00\xCF", None).unwrap();
+        assert_eq!(rar4.format, Format::Rar);
+
+        // Unrelated formats filed under one extension key stay distinct.
+        let yaz0 = analyze_bytes(b"Yaz0\x00\x00\x10\x00", None).unwrap();
+        assert_eq!((yaz0.format, yaz0.extension), (Format::Yaz0, ".arc"));
+        let freearc = analyze_bytes(b"ArC\x01\x00\x00", None).unwrap();
+        assert_eq!(freearc.format, Format::Freearc);
+        let links = analyze_bytes(b"LSIs0000", None).unwrap();
+        assert_eq!(links.format, Format::LsLinksArchive);
+        let crx = analyze_bytes(b"Cr24\x03\x00\x00\x00", None).unwrap();
+        assert_eq!(crx.format, Format::Crx);
+        let arc = analyze_bytes(&[], Some("old.arc")).unwrap();
+        assert_eq!(arc.format, Format::Arc);
+        assert_eq!(arc.description, "ARC compressed archive");
+
+        // Keys naming the same format share it.
+        assert_eq!(extension_info("x.tgz").unwrap().format, Format::TarGz);
+        assert_eq!(extension_info("libfoo.a").unwrap().format, Format::Ar);
+
+        let tarball = analyze_bytes(&[], Some("backup.tar.gz")).unwrap();
+        assert_eq!(tarball.format, Format::TarGz);
+        assert_eq!(
+            analyze_bytes(&[], Some("x.paq8px")).unwrap().format,
+            Format::Paq
+        );
+
+        for &format in Format::ALL {
+            assert_eq!(Format::from_id(format.id()), Some(format));
+        }
+        assert_eq!(Format::from_id("not_a_format"), None);
+    }
+
+    #[test]
+    fn test_mime_types() {
+        let xz = analyze_bytes(&[0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00, 0x00, 0x04], None).unwrap();
+        assert_eq!(xz.mime_type, Some("application/x-xz"));
+        assert_eq!(xz.category, "archive/stream-compression");
+
+        let deb = analyze_bytes(&[], Some("pkg_1.0_amd64.deb")).unwrap();
+        assert_eq!(deb.mime_type, Some("application/vnd.debian.binary-package"));
+        assert_eq!(deb.category, "archive/package");
+
+        let tarball = analyze_bytes(&[], Some("backup.tar.zst")).unwrap();
+        assert_eq!(tarball.mime_type, Some("application/zstd"));
+
+        let game = analyze_bytes(&[], Some("level.xp3")).unwrap();
+        assert_eq!(game.mime_type, None, "No registered type for niche formats");
+
+        // Media types belong to formats, not to the keys they are filed under.
+        let rar5 = analyze_bytes(b"Rar!\x1A\x07\x01\x00", None).unwrap();
+        assert_eq!(rar5.mime_type, Some("application/vnd.rar"));
+        let links = analyze_bytes(b"LSIs0000", None).unwrap();
+        assert_eq!(links.mime_type, None);
+        let jarcs = analyze_bytes(b"JARCS\x00\x00\x00", None).unwrap();
+        assert_eq!(jarcs.mime_type, None);
+        let apm = analyze_bytes(b"ER\x02\x00\x00\x00", Some("disk.iso")).unwrap();
+        assert_eq!(apm.format, Format::ApplePartitionMap);
+        assert_eq!(apm.mime_type, None);
+        let crx = analyze_bytes(b"Cr24\x03\x00\x00\x00", None).unwrap();
+        assert_eq!(crx.mime_type, Some("application/x-chrome-extension"));
+    }
+
+    #[test]
+    fn test_format_extensions() {
+        let zip = analyze_bytes(&[0x50, 0x4B, 0x03, 0x04], Some("upload.dat")).unwrap();
+        assert_eq!(zip.canonical_extension(), ".zip");
+        assert_eq!(zip.format, Format::Zip);
+        assert_eq!(zip.mime_type, Some("application/zip"));
+        for ext in [".jar", ".apk", ".appx"] {
+            assert!(zip.extensions.contains(&ext), "{} should be listed", ext);
+        }
+
+        let xar = analyze_bytes(b"xar!\x00\x1C", None).unwrap();
+        assert_eq!(xar.extensions, [".xar", ".pkg"]);
+
+        let ext = analyze_bytes(&[], Some("library.JAR")).unwrap();
+        assert_eq!(ext.extensions, [".jar"]);
+        assert_eq!(ext.canonical_extension(), ".jar");
+    }
+
+    #[test]
+    fn test_check_extension() {
+        let mut tar = vec![0u8; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+        let gzip = [0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03];
+        let cases: [(&str, &[u8]); 6] = [
+            ("test_check_real.tar.gz", &gzip),
+            ("test_check_plain.tar.gz", &tar),
+            ("test_check_lib.jar", &[0x50, 0x4B, 0x03, 0x04]),
+            ("test_check_blob", &gzip),
+            ("test_check_empty.zip", &[]),
+            ("test_check_notes.txt", b"hello"),
+        ];
+        let mut results = Vec::new();
+        for (filename, data) in cases {
+            File::create(filename).unwrap().write_all(data).unwrap();
+            results.push(check_extension(filename));
+            std::fs::remove_file(filename).unwrap();
+        }
+
+        assert!(matches!(results[0], Ok(ExtensionCheck::Match { .. })));
+        match &results[1] {
+            Ok(ExtensionCheck::Mismatch { magic, extension }) => {
+                assert_eq!(magic.format, Format::Tar);
+                assert_eq!(extension.format, Format::TarGz);
+            }
+            other => panic!("Plain tar named .tar.gz should mismatch, got {:?}", other),
+        }
+        match &results[2] {
+            Ok(ExtensionCheck::Match { extension, .. }) => {
+                assert_eq!(extension.format, Format::Jar)
+            }
+            other => panic!("ZIP named .jar should match, got {:?}", other),
+        }
+        assert!(matches!(results[3], Ok(ExtensionCheck::MagicOnly(_))));
+        assert!(matches!(results[4], Ok(ExtensionCheck::ExtensionOnly(_))));
+        assert_eq!(results[5].as_ref().unwrap(), &ExtensionCheck::Neither);
+
+        assert!(check_extension("does_not_exist.zip").is_err());
+    }
+
+    #[test]
+    fn test_detect_options() {
+        let filename = "test_detect_options.zip";
+        File::create(filename).unwrap();
+        let default = try_analyze(filename).unwrap();
+        let magic_only = try_analyze_with(filename, DetectOptions::magic_only());
+        let lenient = analyze_with(filename, DetectOptions::magic_only());
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(default.unwrap().method(), Method::Extension);
+        assert!(matches!(magic_only, Err(DetectError::TooShort(0))));
+        assert!(lenient.is_none(), "Empty file is not a ZIP by content");
+
+        let by_name = try_analyze_with("missing.tar.zst", DetectOptions::extension_only());
+        assert_eq!(by_name.unwrap().unwrap().format, Format::TarZst);
+
+        let mut tar = vec![0u8; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+        let info = analyze_bytes(&tar, None).unwrap();
+        assert_eq!(info.method(), Method::Magic);
+    }
+
+    #[test]
+    fn test_explain() {
+        let filename = "test_explain.backup.tar.gz";
+        File::create(filename)
+            .unwrap()
+            .write_all(b"plain text")
+            .unwrap();
+        let by_name = explain(filename, DetectOptions::default());
+        std::fs::remove_file(filename).unwrap();
+
+        let by_name = by_name.unwrap();
+        assert!(by_name.candidates.is_empty());
+        let tried: Vec<_> = by_name.suffixes.iter().map(|s| s.suffix.as_str()).collect();
+        assert_eq!(tried, [".backup.tar.gz", ".tar.gz"]);
+        assert_eq!(by_name.result.unwrap().format, Format::TarGz);
+
+        let filename = "test_explain.bin";
+        File::create(filename)
+            .unwrap()
+            .write_all(&[0x50, 0x4B, 0x03, 0x04])
+            .unwrap();
+        let by_magic = explain(filename, DetectOptions::default());
+        std::fs::remove_file(filename).unwrap();
+
+        let by_magic = by_magic.unwrap();
+        assert!(by_magic.suffixes.is_empty(), "Magic match skips the lookup");
+        let selected = &by_magic.candidates[0];
+        assert_eq!(selected.verdict, Verdict::Selected);
+        assert_eq!(selected.info.extension, ".zip");
+        assert!(
+            by_magic.candidates[1..]
+                .iter()
+                .all(|c| c.verdict == Verdict::Outranked)
+        );
+
+        let filename = "test_explain.dat";
+        File::create(filename)
+            .unwrap()
+            .write_all(&[0x1B, 0x00])
+            .unwrap();
+        let rejected = explain(filename, DetectOptions::default());
+        std::fs::remove_file(filename).unwrap();
+
+        let rejected = rejected.unwrap();
+        assert!(rejected.result.is_none());
+        assert!(rejected.candidates.iter().any(|c| {
+            c.verdict == Verdict::MissingExtension
+                && c.info.description == "ArcMac compressed archive"
+        }));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize_archive_info() {
+        let info = analyze_bytes(&[0x50, 0x4B, 0x03, 0x04], None).unwrap();
+        let value = serde_yaml::to_value(&info).unwrap();
+        assert_eq!(value["format"], "zip");
+        assert_eq!(value["mime_type"], "application/zip");
+        assert_eq!(value["hexdump"], "50 4B 03 04");
+        assert_eq!(value["offset"], 0);
+        assert_eq!(value["method"], "magic");
+
+        let info = analyze_bytes(&[], Some("a.tar.gz")).unwrap();
+        let value = serde_yaml::to_value(&info).unwrap();
+        assert!(value["offset"].is_null());
+        assert_eq!(value["method"], "extension");
+    }
+
+    #[test]
+    fn test_analyze_many_keeps_order() {
+        let mut paths = Vec::new();
+        for i in 0..20 {
+            let filename = format!("test_analyze_many_{}.bin", i);
+            let data: &[u8] = if i % 2 == 0 {
+                &[0x50, 0x4B, 0x03, 0x04]
+            } else {
+                b"plain text"
+            };
+            File::create(&filename).unwrap().write_all(data).unwrap();
+            paths.push(filename);
+        }
+        paths.push("test_analyze_many_missing.bin".to_string());
+
+        let parallel = analyze_many(&paths, 4);
+        let sequential = analyze_many(&paths, 1);
+        for path in &paths[..20] {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        assert_eq!(parallel.len(), paths.len());
+        for (i, (a, b)) in parallel.iter().zip(&sequential).enumerate() {
+            match (a, b) {
+                (Ok(a), Ok(b)) => assert_eq!(a, b),
+                (Err(_), Err(_)) => assert_eq!(i, 20),
+                _ => panic!("Results differ for {}", paths[i]),
+            }
+        }
+        assert!(parallel[0].as_ref().unwrap().is_some());
+        assert!(parallel[1].as_ref().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_detector_analyze_many_uses_options() {
+        let paths = [
+            "test_detector_many_a.zip",
+            "test_detector_many_b.zip",
+            "test_detector_many_c.zip",
+        ];
+        for path in &paths {
+            File::create(path)
+                .unwrap()
+                .write_all(b"plain text")
+                .unwrap();
+        }
+
+        let detector = Detector::default().with_options(DetectOptions::magic_only());
+        let strict = detector.analyze_many(&paths, 2);
+        let lenient = analyze_many(&paths, 2);
+        for path in &paths {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        assert!(
+            strict
+                .iter()
+                .all(|result| result.as_ref().unwrap().is_none())
+        );
+        assert!(
+            lenient
+                .iter()
+                .all(|result| result.as_ref().unwrap().is_some())
+        );
+    }
+
+    #[test]
+    fn test_compound_signatures() {
+        let spark = analyze_bytes(&[0x1A, 0x82], Some("games.arc")).unwrap();
+        assert_eq!(spark.description, "Acorn Spark Archive");
+        assert_eq!(spark.offset, Some(0));
+
+        let plain = analyze_bytes(&[0x1A, 0x02], Some("games.arc")).unwrap();
+        assert_eq!(plain.offset, None, "Second condition must also hold");
+        assert!(
+            analyze_bytes(&[0x1A, 0x82], Some("games.bin")).is_none(),
+            "Single-byte magics need their extension"
+        );
+
+        let arcmac = analyze_bytes(&[0x1B, 0x00], Some("GAMES.ARC")).unwrap();
+        assert_eq!(arcmac.description, "ArcMac compressed archive");
+        assert_eq!(arcmac.offset, Some(0));
+    }
+
+    #[test]
+    fn test_end_anchored_signatures() {
+        // Self-extractor stub followed by an empty ZIP's end of central directory.
+        let mut sfx = b"MZ self-extracting stub".repeat(300);
+        let mut eocd = vec![0u8; 22];
+        eocd[..4].copy_from_slice(&[0x50, 0x4B, 0x05, 0x06]);
+        sfx.extend_from_slice(&eocd);
+        let info = analyze_bytes(&sfx, None).unwrap();
+        assert_eq!(info.extension, ".zip");
+        assert!(info.description.contains("end of central directory"));
+
+        let filename = "test_end_anchored.img";
+        {
+            let mut file = File::create(filename).unwrap();
+            file.write_all(&vec![0xAA; 10_000]).unwrap();
+            let mut koly = vec![0u8; 512];
+            koly[..4].copy_from_slice(b"koly");
+            koly[4..8].copy_from_slice(&4u32.to_be_bytes());
+            koly[8..12].copy_from_slice(&512u32.to_be_bytes());
+            file.write_all(&koly).unwrap();
+        }
+        let result = analyze(filename);
+        std::fs::remove_file(filename).unwrap();
+        let info = result.expect("koly trailer should be detected");
+        assert_eq!(info.extension, ".dmg");
+        assert!(info.confidence > 0.5, "Valid trailer should pass its check");
+    }
+
+    #[test]
+    fn test_deep_offset_with_short_reads() {
+        use std::io::{Cursor, SeekFrom};
+
+        // Hands out at most 7 bytes per read, like a pipe or network filesystem.
+        struct Trickle(Cursor<Vec<u8>>);
+
+        impl Read for Trickle {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let len = buf.len().min(7);
+                self.0.read(&mut buf[..len])
+            }
+        }
+
+        impl Seek for Trickle {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.0.seek(pos)
+            }
+        }
+
+        // Third ISO 9660 volume descriptor slot, past the old 34000 byte buffer.
+        let mut image = vec![0u8; 36870];
+        image[36865..36870].copy_from_slice(b"CD001");
+        let info = analyze_reader(Trickle(Cursor::new(image)), None).unwrap();
+        assert_eq!(
+            info.map(|i| i.description),
+            Some("ISO 9660 Volume Descriptor")
+        );
+
+        let mut tar = vec![0u8; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+        let info = analyze_reader(Trickle(Cursor::new(tar)), None).unwrap();
+        assert_eq!(info.map(|i| i.extension), Some(".tar"));
+    }
+
+    #[test]
+    fn test_analyze_stream() {
+        let mut image = vec![0u8; 36870];
+        image[36865..36870].copy_from_slice(b"CD001");
+        let info = analyze_stream(image.as_slice(), None).unwrap();
+        assert_eq!(
+            info.map(|i| i.description),
+            Some("ISO 9660 Volume Descriptor")
+        );
+
+        // A trailer far past the head window is still seen.
+        let mut dmg = vec![0xAA; 200_000];
+        let mut koly = vec![0u8; 512];
+        koly[..4].copy_from_slice(b"koly");
+        koly[4..8].copy_from_slice(&4u32.to_be_bytes());
+        koly[8..12].copy_from_slice(&512u32.to_be_bytes());
+        dmg.extend_from_slice(&koly);
+        let info = analyze_stream(dmg.as_slice(), None).unwrap().unwrap();
+        assert_eq!(info.extension, ".dmg");
+        assert!(info.confidence > 0.5, "Valid trailer should pass its check");
+
+        let info = analyze_stream(&[0x28, 0xB5, 0x2F, 0xFD][..], None).unwrap();
+        assert!(info.is_some());
+        assert!(matches!(
+            analyze_stream(&[][..], None),
+            Err(DetectError::TooShort(0))
+        ));
+        assert_eq!(
+            analyze_stream(&[][..], Some("empty.zip"))
+                .unwrap()
+                .unwrap()
+                .extension,
+            ".zip"
+        );
+    }
+
+    #[test]
+    fn test_supported_formats() {
+        let formats: Vec<&FormatInfo> = supported_formats().collect();
+        assert_eq!(formats.len(), Format::ALL.len());
+        for format in Format::ALL {
+            assert_eq!(formats.iter().filter(|f| f.format == *format).count(), 1);
+        }
+
+        let gz = formats.iter().find(|f| f.format == Format::Gz).unwrap();
+        assert_eq!(gz.extension, ".gz");
+        assert_eq!(gz.category, "archive/stream-compression");
+        assert!(gz.signatures > 0);
+        assert!(
+            formats
+                .iter()
+                .any(|f| f.signatures == 0 && generated::get_extension_info(f.extension).is_some())
+        );
+
+        // Every key the extension fallback uses is listed with its format.
+        let listed = |key: &str, format: Format| {
+            formats
+                .iter()
+                .any(|f| f.format == format && f.extensions.contains(&key))
+        };
+        for (key, info) in generated::EXTENSION_PATTERNS {
+            assert!(listed(key, info.format), "{} is not listed", key);
+        }
+        for key in [".tgz", ".tbz2", ".txz", ".lzh", ".f"] {
+            let info = generated::get_extension_info(key).unwrap();
+            assert!(listed(key, info.format), "{} is not listed", key);
+        }
+
+        let lzh = formats.iter().find(|f| f.format == Format::Lzh).unwrap();
+        assert_eq!(lzh.extension, ".lzh");
+        assert_eq!(lzh.description, "LHA compressed archive");
+        assert!(lzh.extensions.contains(&".lha"));
+        let freeze = formats.iter().find(|f| f.format == Format::Freeze).unwrap();
+        assert_eq!(freeze.extensions, [".f"]);
+        let tar_gz = formats.iter().find(|f| f.format == Format::TarGz).unwrap();
+        assert_eq!(tar_gz.extensions, [".tar.gz", ".tgz"]);
+    }
+
+    #[test]
+    fn test_signature_db() {
+        const ACME: &str = r#"
+containers:
+  .acme:
+    mime_type: "application/x-acme"
+    signatures:
+      - description: "ACME asset bundle"
+        hexdump: "41 43 4D 45 ?? 01"
+  .acmeidx:
+    signatures: []
+archiveAndCompress:
+  .acmezip:
+    signatures:
+      - description: "ACME ZIP-based package"
+        hexdump: "50 4B 03 04"
+  .zip:
+    signatures:
+      - description: "ZIP archive written by ACME tools"
+        hexdump: "50 4B 03 04 41 43"
+"#;
+
+        // Loading nothing keeps the built-in ranking.
+        let mut db = SignatureDb::builtin();
+        db.load_str("").unwrap();
+        assert_eq!(db.signatures().count(), generated::SIGNATURES.len());
+        assert!(
+            db.signatures()
+                .zip(generated::SIGNATURES)
+                .all(|(a, b)| std::ptr::eq(a, b))
+        );
+
+        db.load_str(ACME).unwrap();
+        let detector = Detector::new(db);
+
+        let info = detector.analyze_bytes(b"ACME\x07\x01", None).unwrap();
+        assert_eq!(info.format, Format::Custom);
+        assert_eq!(info.format.id(), "custom");
+        assert_eq!(info.extension, ".acme");
+        assert_eq!(info.category, "archive/container");
+        assert_eq!(info.mime_type, Some("application/x-acme"));
+
+        let info = detector.analyze_bytes(&[], Some("index.ACMEIDX")).unwrap();
+        assert_eq!(info.extension, ".acmeidx");
+        assert_eq!(info.method(), Method::Extension);
+
+        // On a tie with a built-in signature, the loaded one wins.
+        let info = detector
+            .analyze_bytes(&[0x50, 0x4B, 0x03, 0x04], None)
+            .unwrap();
+        assert_eq!(info.extension, ".acmezip");
+
+        // Signatures filed under a built-in key keep its format and mime type.
+        let info = detector
+            .analyze_bytes(&[0x50, 0x4B, 0x03, 0x04, 0x41, 0x43], None)
+            .unwrap();
+        assert_eq!(info.format, Format::Zip);
+        assert_eq!(info.mime_type, Some("application/zip"));
+
+        // The built-in database is untouched.
+        assert_eq!(
+            analyze_bytes(b"ACME\x07\x01", Some("x.acme")),
+            None,
+            "loaded signatures must not leak into the free functions"
+        );
+
+        // Reloading a file shares the data loaded the first time.
+        let mut again = SignatureDb::empty();
+        again.load_str(ACME).unwrap();
+        let mut twice = SignatureDb::empty();
+        twice.load_str(ACME).unwrap();
+        assert!(
+            again
+                .signatures()
+                .zip(twice.signatures())
+                .all(|(a, b)| std::ptr::eq(a, b))
+        );
+
+        let mut empty = SignatureDb::empty();
+        empty.load_str(ACME).unwrap();
+        let detector = Detector::new(empty);
+        assert!(
+            detector
+                .analyze_bytes(&[0x1F, 0x8B, 0x08, 0x00], Some("a.gz"))
+                .is_none()
+        );
+        assert!(detector.analyze_bytes(b"ACME\x00\x01", None).is_some());
+    }
+
+    #[test]
+    fn test_signature_db_short_signatures() {
+        // 1A plus a single masked bit is 9 bits: too weak on its own.
+        let nine_bits = "containers:\n  .spk:\n    signatures:\n      - description: \"x\"\n        hexdump: \"1A\"\n        and:\n          - hexdump: \"80\"\n            mask: \"80\"\n            offset: 1\n";
+        let mut db = SignatureDb::empty();
+        db.load_str(nine_bits).unwrap();
+        assert_eq!(db.signatures().count(), 0);
+
+        let gated = nine_bits.replace("and:", "extension_required: true\n        and:");
+        db.load_str(&gated).unwrap();
+        assert_eq!(db.signatures().count(), 1);
+
+        let two_bytes = "containers:\n  .spk:\n    signatures:\n      - description: \"x\"\n        hexdump: \"1A 80\"\n";
+        let mut db = SignatureDb::empty();
+        db.load_str(two_bytes).unwrap();
+        assert_eq!(db.signatures().count(), 1);
+    }
+
+    #[test]
+    fn test_signature_db_and_items() {
+        // Keys of an and: item may come in any order, and a less indented
+        // key returns to the signature.
+        let yaml = "# In-house formats\ncontainers:\n  .spk:\n    signatures:\n      - description: \"x\"\n        hexdump: \"1A 80\"\n        and:\n          - offset: 8\n            hexdump: \"55 AA\"\n        extension_required: true\n";
+        let mut db = SignatureDb::empty();
+        db.load_str(yaml).unwrap();
+        let detector = Detector::new(db);
+        let data = b"\x1A\x80\x00\x00\x00\x00\x00\x00\x55\xAA";
+        let info = detector.analyze_bytes(data, Some("a.spk")).unwrap();
+        assert_eq!(info.method(), Method::Magic);
+        assert!(detector.analyze_bytes(data, None).is_none());
+        let info = detector.analyze_bytes(&data[..8], Some("a.spk")).unwrap();
+        assert_eq!(info.method(), Method::Extension);
+    }
+
+    #[test]
+    fn test_signature_db_formats() {
+        let mut db = SignatureDb::empty();
+        db.load_str(
+            "containers:\n  .acme:\n    signatures:\n      - description: \"ACME bundle\"\n        hexdump: \"41 43 4D 45\"\n      - description: \"Zipped ACME bundle\"\n        format: zip\n        hexdump: \"41 43 5A 50\"\n",
+        )
+        .unwrap();
+        let detector = Detector::new(db);
+        assert_eq!(
+            detector.analyze_bytes(b"ACME", None).unwrap().format,
+            Format::Custom
+        );
+        assert_eq!(
+            detector.analyze_bytes(b"ACZP", None).unwrap().format,
+            Format::Zip
+        );
+
+        for (yaml, line) in [
+            (
+                "containers:\n  .acme:\n    format: Acme\n    signatures: []\n",
+                3,
+            ),
+            ("containers:\n  .7acme:\n    signatures: []\n", 2),
+            (
+                "containers:\n  .acme:\n    format: acme\n    signatures: []\npackaging:\n  .acme:\n    format: other\n    signatures: []\n",
+                6,
+            ),
+        ] {
+            match SignatureDb::empty().load_str(yaml) {
+                Err(SignatureDbError::Invalid(message)) => {
+                    assert!(
+                        message.starts_with(&format!("line {}:", line)),
+                        "{}",
+                        message
+                    )
+                }
+                other => panic!("expected an invalid database, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_signature_db_errors() {
+        let mut db = SignatureDb::builtin();
+        let bad_hex = "packaging:\n  .pkgx:\n    signatures:\n      - description: \"x\"\n        hexdump: \"5G 4B\"\n";
+        match db.load_str(bad_hex) {
+            Err(SignatureDbError::Invalid(message)) => {
+                assert!(message.starts_with("line 5:"), "{}", message)
+            }
+            other => panic!("expected an invalid database, got {:?}", other),
+        }
+
+        // Anything the parser does not recognize is rejected, not skipped.
+        let signature = "packaging:\n  .pkgx:\n    signatures:\n      - description: \"x\"\n";
+        for (yaml, line) in [
+            (format!("{}        hexdump: 50 4B 03 04\n", signature), 5),
+            (format!("{}        hexdump: '50 4B 03 04'\n", signature), 5),
+            (format!("{}        hexdmp: \"50 4B 03 04\"\n", signature), 5),
+            (
+                format!(
+                    "{}        hexdump: \"50 4B\"\n        offset: 0x10\n",
+                    signature
+                ),
+                6,
+            ),
+            (
+                format!(
+                    "{}        hexdump: \"50 4B\"\n        offset_range: [0, 1k]\n",
+                    signature
+                ),
+                6,
+            ),
+            (
+                format!(
+                    "{}        hexdump: \"50 4B\"\n        extension_required: yes\n",
+                    signature
+                ),
+                6,
+            ),
+            (
+                format!(
+                    "{}        hexdump: \"50 4B\"\n        and:\n          - hexdump: \"03\"\n            format: zip\n",
+                    signature
+                ),
+                8,
+            ),
+            ("packaging:\n  .pkgx:\n    mimetype: \"x\"\n".to_string(), 3),
+            (
+                format!(
+                    "{}        hexdump: \"\"\n        offset_range: [0, 10]\n        and:\n          - hexdump: \"41 42\"\n",
+                    signature
+                ),
+                5,
+            ),
+            (format!("packagng:\n{}", &signature[11..]), 1),
+        ] {
+            match db.load_str(&yaml) {
+                Err(SignatureDbError::Invalid(message)) => {
+                    assert!(
+                        message.starts_with(&format!("line {}:", line)),
+                        "{}",
+                        message
+                    )
+                }
+                other => panic!("expected an invalid database, got {:?}", other),
+            }
+        }
+
+        // Signatures reaching absurdly far into a file are refused before
+        // any reader tries to buffer that much.
+        for position in [
+            "offset: 9223372036854775807",
+            "offset: -9223372036854775808",
+            "offset_range: [0, 18446744073709551615]",
+        ] {
+            let yaml = format!(
+                "{}        hexdump: \"50 4B\"\n        {}\n",
+                signature, position
+            );
+            match db.load_str(&yaml) {
+                Err(SignatureDbError::Invalid(message)) => {
+                    assert!(message.contains("reaches further"), "{}", message)
+                }
+                other => panic!("expected an invalid database, got {:?}", other),
+            }
+        }
+
+        let outside = ".pkgx:\n  signatures: []\n";
+        assert!(matches!(
+            db.load_str(outside),
+            Err(SignatureDbError::Invalid(_))
+        ));
+        assert!(matches!(
+            db.load("no_such_signatures.yaml"),
+            Err(SignatureDbError::Io(_))
+        ));
+    }
 }
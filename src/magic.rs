@@ -1,19 +1,616 @@
-use crate::ArchiveInfo;
+use crate::{ArchiveInfo, DetectError, Format, schema};
+use std::borrow::Cow;
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Read, Seek, SeekFrom};
 use std::path::Path;
+use std::sync::OnceLock;
+
+/// An additional byte pattern a compound signature requires, positioned
+/// relative to where the signature's primary bytes matched.
+#[derive(PartialEq, Eq, Hash)]
+pub struct BytePattern {
+    pub bytes: &'static [u8],
+    pub mask: Option<&'static [u8]>,
+    pub offset: usize,
+}
+
+impl BytePattern {
+    fn significant_len(&self) -> usize {
+        significant_len(self.bytes, self.mask)
+    }
+}
+
+/// A magic number signature compiled in from `archive_signatures.yaml`.
+#[derive(PartialEq, Eq, Hash)]
+pub struct MagicSignature {
+    pub format: Format,
+    pub bytes: &'static [u8],
+    /// Per-byte bitmask applied before comparing, or `None` for an exact
+    /// match. A `0x00` mask byte is a wildcard.
+    pub mask: Option<&'static [u8]>,
+    /// Position of the signature, counted from the start of the file, or
+    /// back from the end of the file when `from_end` is set.
+    pub offset: usize,
+    pub from_end: bool,
+    /// Number of further start positions after `offset` to search, for
+    /// markers at a variable position. Zero for fixed-offset signatures.
+    pub search: usize,
+    /// Further patterns that must all match for the signature to match.
+    pub and: &'static [BytePattern],
+    /// Only match files whose name ends in `extension`, for signatures too
+    /// short to be trusted on their own.
+    pub extension_required: bool,
+    pub category: &'static str,
+    pub mime_type: Option<&'static str>,
+    pub description: &'static str,
+    pub hexdump: &'static str,
+    pub extension: &'static str,
+    /// Every extension whose entry lists this same pattern, canonical first.
+    pub extensions: &'static [&'static str],
+}
+
+impl MagicSignature {
+    /// Returns `true` if `data` holds this signature's bytes at its offset.
+    pub fn matches(&self, data: &Sample) -> bool {
+        self.find(data).is_some()
+    }
+
+    /// Returns the absolute position at which `data` holds this signature,
+    /// scanning the whole search window for ranged signatures.
+    pub fn find(&self, data: &Sample) -> Option<usize> {
+        // Databases reject empty patterns; one built by hand matches nothing.
+        if self.bytes.is_empty() {
+            return None;
+        }
+        let first = self.position(data.len())?;
+        if self.search == 0 {
+            let window = data.get(first, self.bytes.len())?;
+            let found = masked_eq(window, self.bytes, self.mask) && self.and_matches(data, first);
+            return found.then_some(first);
+        }
+
+        let haystack = data.get_upto(first, self.search.saturating_add(self.bytes.len()))?;
+        haystack
+            .windows(self.bytes.len())
+            .enumerate()
+            .filter(|(_, window)| masked_eq(window, self.bytes, self.mask))
+            .map(|(pos, _)| first + pos)
+            .find(|&start| self.and_matches(data, start))
+    }
+
+    fn and_matches(&self, data: &Sample, start: usize) -> bool {
+        self.and.iter().all(|pattern| {
+            start
+                .checked_add(pattern.offset)
+                .and_then(|offset| data.get(offset, pattern.bytes.len()))
+                .is_some_and(|window| masked_eq(window, pattern.bytes, pattern.mask))
+        })
+    }
+
+    /// Returns `false` if the signature requires an extension that `name`
+    /// lacks.
+    pub fn admits(&self, name: Option<&str>) -> bool {
+        !self.extension_required || name.is_some_and(|name| has_extension(name, self.extension))
+    }
+
+    /// Absolute position of the signature (or the start of its search
+    /// window) within a file of `len` bytes.
+    pub fn position(&self, len: u64) -> Option<usize> {
+        if self.from_end {
+            usize::try_from(len).ok()?.checked_sub(self.offset)
+        } else {
+            Some(self.offset)
+        }
+    }
+
+    /// Number of bytes that are not wildcards, across all patterns.
+    pub fn significant_len(&self) -> usize {
+        significant_len(self.bytes, self.mask)
+            + self
+                .and
+                .iter()
+                .map(BytePattern::significant_len)
+                .sum::<usize>()
+    }
+
+    /// Number of bits compared across all patterns, the measure signatures
+    /// are ranked by.
+    pub fn significant_bits(&self) -> u32 {
+        significant_bits(self.bytes, self.mask)
+            + self
+                .and
+                .iter()
+                .map(|pattern| significant_bits(pattern.bytes, pattern.mask))
+                .sum::<u32>()
+    }
+
+    /// Size of the smallest file that can hold this signature.
+    pub fn min_len(&self) -> usize {
+        if self.from_end {
+            self.offset
+        } else {
+            self.offset.saturating_add(self.bytes.len())
+        }
+    }
+
+    /// Builds the detection result for this signature matched at `start`,
+    /// scoring it against `data` and the file's `name`, if known.
+    pub fn info(&self, data: &Sample, start: usize, name: Option<&str>) -> ArchiveInfo {
+        ArchiveInfo {
+            format: self.format,
+            category: self.category,
+            mime_type: self.mime_type,
+            description: self.description,
+            hexdump: self.hexdump,
+            extension: self.extension,
+            extensions: self.extensions,
+            confidence: self.confidence(data, start, name),
+            offset: Some(start as u64),
+        }
+    }
+
+    /// Scores how much a match of this signature can be trusted.
+    ///
+    /// Longer signatures are less likely to match by accident, as are those
+    /// anchored at a non-zero offset, while searching a range of offsets
+    /// makes an accidental match more likely. A filename ending in the signature's
+    /// extension and a passing structural check raise the score, while a
+    /// failing structural check lowers it sharply.
+    fn confidence(&self, data: &Sample, start: usize, name: Option<&str>) -> f32 {
+        let mut score = self.significant_len().min(8) as f32 / 8.0 * 0.6;
+        if self.search > 0 {
+            score -= 0.1;
+        } else if self.offset > 0 {
+            score += 0.1;
+        }
+        if name.is_some_and(|name| self.extension_agrees(name)) {
+            score += 0.2;
+        }
+        match crate::structure::validate(self, data, start) {
+            Some(true) => score += 0.2,
+            Some(false) => score -= 0.3,
+            None => {}
+        }
+        score.clamp(0.0, 1.0)
+    }
+
+    /// Returns `true` if `name` ends in any extension that uses this same
+    /// byte pattern, e.g. `.jar` for the ZIP header.
+    fn extension_agrees(&self, name: &str) -> bool {
+        self.extensions.iter().any(|ext| has_extension(name, ext))
+    }
+}
+
+fn masked_eq(window: &[u8], bytes: &[u8], mask: Option<&[u8]>) -> bool {
+    match mask {
+        None => window == bytes,
+        Some(mask) => window
+            .iter()
+            .zip(mask)
+            .zip(bytes)
+            .all(|((byte, mask), expected)| byte & mask == *expected),
+    }
+}
+
+fn significant_len(bytes: &[u8], mask: Option<&[u8]>) -> usize {
+    match mask {
+        Some(mask) => mask.iter().filter(|&&m| m != 0).count(),
+        None => bytes.len(),
+    }
+}
+
+fn significant_bits(bytes: &[u8], mask: Option<&[u8]>) -> u32 {
+    match mask {
+        Some(mask) => mask.iter().map(|m| m.count_ones()).sum(),
+        None => bytes.len() as u32 * 8,
+    }
+}
+
+/// Confidence reported for results based on the filename extension alone.
+pub const EXTENSION_CONFIDENCE: f32 = 0.1;
+
+/// Returns `true` if `name` ends in `ext`, which may be a glob pattern.
+pub(crate) fn has_extension(name: &str, ext: &str) -> bool {
+    name.match_indices('.')
+        .any(|(i, _)| extension_matches(ext, &name[i..]))
+}
+
+/// Matches an extension against a key from the signature database, ignoring
+/// case.
+///
+/// Keys may be glob patterns: `?` matches any character, `#` any digit and
+/// `*` any run of characters. Wildcards never match a `.`, so a pattern only
+/// covers a single extension component.
+pub(crate) fn extension_matches(pattern: &str, ext: &str) -> bool {
+    fn glob(pattern: &[char], ext: &[char]) -> bool {
+        match (pattern.first(), ext.first()) {
+            (None, None) => true,
+            (Some('*'), _) => {
+                glob(&pattern[1..], ext)
+                    || (ext.first().is_some_and(|&c| c != '.') && glob(pattern, &ext[1..]))
+            }
+            (Some('?'), Some(&c)) => c != '.' && glob(&pattern[1..], &ext[1..]),
+            (Some('#'), Some(c)) => c.is_ascii_digit() && glob(&pattern[1..], &ext[1..]),
+            (Some(p), Some(c)) => p == c && glob(&pattern[1..], &ext[1..]),
+            _ => false,
+        }
+    }
+
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let ext: Vec<char> = ext.to_lowercase().chars().collect();
+    glob(&pattern, &ext)
+}
+
+/// Number of bytes read contiguously from the start of every file.
+///
+/// Signatures ending beyond this, including those anchored to the end of the
+/// file, are fetched individually by seeking.
+const HEAD_LEN: usize = 4096;
+
+/// Granularity of the windows fetched for deep-offset and end-anchored
+/// signatures, so that structural checks can look at the bytes surrounding a
+/// match.
+const BLOCK_LEN: u64 = 512;
+
+/// The parts of a file that signatures and structural checks look at.
+///
+/// Holds the start of the file plus any blocks fetched further in, so that
+/// deep-offset and end-anchored signatures can be matched without reading
+/// the whole file.
+pub struct Sample<'a> {
+    head: Cow<'a, [u8]>,
+    blocks: Vec<(u64, Vec<u8>)>,
+    len: u64,
+}
+
+impl<'a> Sample<'a> {
+    /// Wraps an in-memory buffer holding the complete file.
+    pub fn from_bytes(data: &'a [u8]) -> Self {
+        Sample {
+            head: Cow::Borrowed(data),
+            blocks: Vec::new(),
+            len: data.len() as u64,
+        }
+    }
+
+    /// Total length of the underlying file.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the underlying file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns up to `len` contiguous bytes at `offset`, stopping early where
+    /// the read data ends.
+    pub fn get_upto(&self, offset: usize, len: usize) -> Option<&[u8]> {
+        let end = offset.saturating_add(len);
+        let from_head = self.head.get(offset..end.min(self.head.len()));
+        let from_blocks = self.blocks.iter().filter_map(|(block_start, block)| {
+            let rel = (offset as u64).checked_sub(*block_start)? as usize;
+            block.get(rel..(rel + len).min(block.len()))
+        });
+        from_head
+            .into_iter()
+            .chain(from_blocks)
+            .max_by_key(|bytes| bytes.len())
+    }
+
+    /// Returns the `len` bytes at `offset`, if they were read.
+    pub fn get(&self, offset: usize, len: usize) -> Option<&[u8]> {
+        let end = offset.checked_add(len)?;
+        if let Some(bytes) = self.head.get(offset..end) {
+            return Some(bytes);
+        }
+
+        self.blocks.iter().find_map(|(block_start, block)| {
+            let rel = (offset as u64).checked_sub(*block_start)? as usize;
+            block.get(rel..rel + len)
+        })
+    }
+}
+
+impl Sample<'static> {
+    /// Reads the head of a stream, then seeks to every deeper window a
+    /// compiled-in signature needs.
+    pub fn read<R: Read + Seek>(reader: &mut R) -> io::Result<Self> {
+        Self::read_for(reader, builtin())
+    }
+
+    /// Like [`Sample::read`], fetching the windows `signatures` need.
+    pub(crate) fn read_for<R: Read + Seek>(
+        reader: &mut R,
+        signatures: &[&MagicSignature],
+    ) -> io::Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        let mut head = vec![0u8; HEAD_LEN.min(len as usize)];
+        let filled = read_full(reader, &mut head)?;
+        head.truncate(filled);
+
+        let mut sample = Sample {
+            head: Cow::Owned(head),
+            blocks: Vec::new(),
+            len,
+        };
+
+        for sig in signatures {
+            let Some(position) = sig.position(len) else {
+                continue;
+            };
+            // Saturate rather than overflow: whatever lies past the end of
+            // the file is not fetched anyway.
+            let start = position as u64;
+            let end = |start: u64, len: usize| {
+                start.saturating_add(sig.search.saturating_add(len) as u64)
+            };
+            sample.fetch(reader, start, end(start, sig.bytes.len()))?;
+            for pattern in sig.and {
+                let start = start.saturating_add(pattern.offset as u64);
+                sample.fetch(reader, start, end(start, pattern.bytes.len()))?;
+            }
+        }
+
+        Ok(sample)
+    }
+
+    /// Reads a stream that cannot seek, such as a pipe, to its end.
+    ///
+    /// Keeps only the head every forward-offset signature fits in and a
+    /// rolling window over the end for end-anchored ones, so memory use stays
+    /// bounded however long the stream is.
+    pub fn read_stream<R: Read>(reader: &mut R) -> io::Result<Self> {
+        Self::read_stream_for(reader, builtin())
+    }
+
+    /// Like [`Sample::read_stream`], keeping the windows `signatures` need.
+    pub(crate) fn read_stream_for<R: Read>(
+        reader: &mut R,
+        signatures: &[&MagicSignature],
+    ) -> io::Result<Self> {
+        let (head_len, tail_len) = stream_window(signatures);
+
+        let mut head = vec![0u8; head_len];
+        let filled = read_full(reader, &mut head)?;
+        head.truncate(filled);
+        let mut len = filled as u64;
+
+        let mut tail = head[filled.saturating_sub(tail_len)..].to_vec();
+        let mut chunk = vec![0u8; 64 * 1024];
+        if filled == head_len {
+            loop {
+                let n = match reader.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => return Err(err),
+                };
+                len += n as u64;
+                tail.extend_from_slice(&chunk[..n]);
+                let excess = tail.len().saturating_sub(tail_len);
+                tail.drain(..excess);
+            }
+        }
+
+        let mut blocks = Vec::new();
+        if len > head.len() as u64 && !tail.is_empty() {
+            blocks.push((len - tail.len() as u64, tail));
+        }
+
+        Ok(Sample {
+            head: Cow::Owned(head),
+            blocks,
+            len,
+        })
+    }
+
+    /// Makes the bytes in `start..end` available, reading the blocks that
+    /// cover them unless the head or an earlier block already does.
+    fn fetch<R: Read + Seek>(&mut self, reader: &mut R, start: u64, end: u64) -> io::Result<()> {
+        let end = end.min(self.len);
+        if end <= self.head.len() as u64 || start >= end {
+            return Ok(());
+        }
+        let covered = self.blocks.iter().any(|(block_start, block)| {
+            *block_start <= start && block_start + block.len() as u64 >= end
+        });
+        if covered {
+            return Ok(());
+        }
+
+        let block_start = start / BLOCK_LEN * BLOCK_LEN;
+        let block_end = end.div_ceil(BLOCK_LEN) * BLOCK_LEN;
+        let mut block = vec![0u8; (block_end.min(self.len) - block_start) as usize];
+        reader.seek(SeekFrom::Start(block_start))?;
+        let filled = read_full(reader, &mut block)?;
+        block.truncate(filled);
+        self.blocks.push((block_start, block));
+        Ok(())
+    }
+}
+
+/// Sizes of the head and end windows [`Sample::read_stream`] keeps: the
+/// furthest any forward-offset signature reaches and the deepest any
+/// end-anchored one starts, rounded up to whole blocks.
+fn stream_window(signatures: &[&MagicSignature]) -> (usize, usize) {
+    let mut head = HEAD_LEN;
+    let mut tail = 0;
+    for sig in signatures {
+        if sig.from_end {
+            tail = tail.max(sig.offset);
+            continue;
+        }
+        let reach = sig
+            .and
+            .iter()
+            .map(|pattern| pattern.offset.saturating_add(pattern.bytes.len()))
+            .chain([sig.bytes.len()])
+            .max()
+            .unwrap_or(0);
+        head = head.max(sig.offset.saturating_add(sig.search).saturating_add(reach));
+    }
+    // Databases cap how far signatures reach; this only guards against
+    // signatures built by hand.
+    let round =
+        |n: usize| n.min(schema::MAX_REACH).div_ceil(BLOCK_LEN as usize) * BLOCK_LEN as usize;
+    (round(head), round(tail))
+}
+
+/// Fills `buf` as far as possible, retrying short reads until end of file.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+/// Returns the most specific signature matching `data`.
+///
+/// `name` is the file's name, if known, and only affects the confidence score.
+pub fn check_magic_signature(data: &[u8], name: Option<&str>) -> Option<ArchiveInfo> {
+    check_sample(&Sample::from_bytes(data), name)
+}
+
+/// Returns every signature matching `data`, most specific first.
+pub fn check_magic_signatures(data: &[u8], name: Option<&str>) -> Vec<ArchiveInfo> {
+    check_sample_all(&Sample::from_bytes(data), name)
+}
+
+/// Returns the most specific signature matching `sample`.
+pub fn check_sample(sample: &Sample, name: Option<&str>) -> Option<ArchiveInfo> {
+    check_sample_in(builtin(), sample, name)
+}
+
+pub(crate) fn check_sample_in(
+    signatures: &[&MagicSignature],
+    sample: &Sample,
+    name: Option<&str>,
+) -> Option<ArchiveInfo> {
+    signatures
+        .iter()
+        .filter(|sig| sig.admits(name))
+        .find_map(|sig| sig.find(sample).map(|start| sig.info(sample, start, name)))
+}
+
+/// Returns every signature matching `sample`, most specific first.
+pub fn check_sample_all(sample: &Sample, name: Option<&str>) -> Vec<ArchiveInfo> {
+    check_sample_all_in(builtin(), sample, name)
+}
+
+pub(crate) fn check_sample_all_in(
+    signatures: &[&MagicSignature],
+    sample: &Sample,
+    name: Option<&str>,
+) -> Vec<ArchiveInfo> {
+    signatures
+        .iter()
+        .filter(|sig| sig.admits(name))
+        .filter_map(|sig| sig.find(sample).map(|start| sig.info(sample, start, name)))
+        .collect()
+}
+
+/// Returns every signature whose bytes match `sample`, most specific first,
+/// along with whether `name` satisfies its extension requirement.
+pub fn trace_sample(sample: &Sample, name: Option<&str>) -> Vec<(ArchiveInfo, bool)> {
+    trace_sample_in(builtin(), sample, name)
+}
+
+pub(crate) fn trace_sample_in(
+    signatures: &[&MagicSignature],
+    sample: &Sample,
+    name: Option<&str>,
+) -> Vec<(ArchiveInfo, bool)> {
+    signatures
+        .iter()
+        .filter_map(|sig| {
+            let start = sig.find(sample)?;
+            Some((sig.info(sample, start, name), sig.admits(name)))
+        })
+        .collect()
+}
+
+/// Length of the shortest compiled-in signature, including its offset.
+pub fn min_signature_len() -> usize {
+    min_signature_len_in(builtin())
+}
+
+pub(crate) fn min_signature_len_in(signatures: &[&MagicSignature]) -> usize {
+    signatures
+        .iter()
+        .map(|sig| sig.min_len())
+        .min()
+        .unwrap_or(0)
+}
+
+/// The compiled-in signatures, most specific first, in the form
+/// [`SignatureDb`](crate::SignatureDb) keeps its signatures.
+pub(crate) fn builtin() -> &'static [&'static MagicSignature] {
+    static BUILTIN: OnceLock<Vec<&'static MagicSignature>> = OnceLock::new();
+    BUILTIN.get_or_init(|| crate::generated::SIGNATURES.iter().collect())
+}
+
+/// Reads the parts of the file signatures need, distinguishing open failures
+/// from read failures.
+pub fn read_sample<P: AsRef<Path>>(path: P) -> Result<Sample<'static>, DetectError> {
+    read_sample_in(builtin(), path.as_ref())
+}
+
+pub(crate) fn read_sample_in(
+    signatures: &[&MagicSignature],
+    path: &Path,
+) -> Result<Sample<'static>, DetectError> {
+    let mut file = File::open(path).map_err(DetectError::Unreadable)?;
+
+    let metadata = file.metadata().map_err(DetectError::Unreadable)?;
+    if metadata.is_dir() {
+        return Err(DetectError::Unreadable(io::Error::new(
+            io::ErrorKind::IsADirectory,
+            "is a directory",
+        )));
+    }
+
+    // FIFOs, character devices and the like cannot seek, or claim a length
+    // of zero, so they are read to the end like a pipe.
+    if !metadata.is_file() || file.seek(SeekFrom::End(0)).is_err() {
+        return Sample::read_stream_for(&mut file, signatures).map_err(DetectError::Io);
+    }
+    Sample::read_for(&mut file, signatures).map_err(DetectError::Io)
+}
+
+/// Reads the parts of a stream signatures need, starting from its beginning.
+pub fn read_sample_from<R: Read + Seek>(reader: &mut R) -> Result<Sample<'static>, DetectError> {
+    Sample::read(reader).map_err(DetectError::Io)
+}
+
+/// Reads the parts of a non-seekable stream signatures need, consuming it to
+/// the end.
+pub fn read_sample_stream<R: Read>(reader: &mut R) -> Result<Sample<'static>, DetectError> {
+    Sample::read_stream(reader).map_err(DetectError::Io)
+}
+
+pub(crate) fn file_name(path: &Path) -> Option<&str> {
+    path.file_name().and_then(|n| n.to_str())
+}
 
 pub fn check_magic<P: AsRef<Path>>(path: P) -> Option<ArchiveInfo> {
-    let mut file = match File::open(path) {
-        Ok(f) => f,
-        Err(_) => return None,
-    };
-
-    let mut buffer = [0u8; 34000];
-    let bytes_read = match file.read(&mut buffer) {
-        Ok(n) => n,
-        Err(_) => return None,
-    };
-
-    crate::generated::check_magic_signature(&buffer[..bytes_read])
+    let path = path.as_ref();
+    let sample = read_sample(path).ok()?;
+    check_sample(&sample, file_name(path))
+}
+
+pub fn check_magic_all<P: AsRef<Path>>(path: P) -> Vec<ArchiveInfo> {
+    let path = path.as_ref();
+    match read_sample(path) {
+        Ok(sample) => check_sample_all(&sample, file_name(path)),
+        Err(_) => Vec::new(),
+    }
 }
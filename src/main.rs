@@ -1,25 +1,96 @@
-use isarchive::analyze;
+use isarchive::{
+    ArchiveInfo, CATEGORIES, DetectError, DetectOptions, Detector, ExtensionCheck, FormatInfo,
+    Method, SignatureDb, SignatureDbError, Verdict, supported_formats,
+};
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::{BTreeMap, HashSet};
 use std::env;
-use std::path::Path;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process;
 
+// The library's worker pool, so that `--jobs` behaves like `analyze_many`.
+mod pool;
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
     let mut files = Vec::new();
     let mut brief = false;
     let mut mime = false;
+    let mut mime_type = false;
+    let mut check_ext = false;
+    let mut magic_only = false;
+    let mut extension_only = false;
+    let mut verbose = false;
+    let mut output = Output::Text;
+    let mut recursive = false;
+    let mut walk = Walk::default();
+    let mut jobs = 1;
+    let mut files_from = Vec::new();
+    let mut null_separated = false;
+    let mut filter = CategoryFilter::default();
+    let mut listing = None;
+    let mut summarize = false;
+    let mut list_formats = false;
+    let mut signature_files = Vec::new();
 
     if args.len() < 2 {
         print_usage_brief(&args[0]);
         process::exit(1);
     }
 
-    for arg in args.iter().skip(1) {
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
         if arg.starts_with("--") {
-            match arg.as_str() {
+            // Options taking a value accept both `--name value` and `--name=value`.
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| rest.next().cloned())
+                    .unwrap_or_else(|| {
+                        eprintln!("Option {} requires a value", name);
+                        process::exit(1);
+                    })
+            };
+            match name {
+                "--recursive" => recursive = true,
+                "--files-from" => files_from.push(value()),
+                "--null" => null_separated = true,
+                "--category" => filter.include.push(parse_category(&value())),
+                "--exclude-category" => filter.exclude.push(parse_category(&value())),
+                "--summary" => summarize = true,
+                "--list-formats" => list_formats = true,
+                "--signatures" => signature_files.push(value()),
+                "--only-archives" => listing = Some(Listing::Archives),
+                "--only-non-archives" => listing = Some(Listing::NonArchives),
+                "--jobs" => jobs = parse_jobs(&value()),
+                "--follow-symlinks" => walk.follow_symlinks = true,
+                "--hidden" => walk.hidden = true,
+                "--include" => walk.include.push(value()),
+                "--exclude" => walk.exclude.push(value()),
+                "--max-depth" => {
+                    let depth = value();
+                    walk.max_depth = Some(depth.parse().unwrap_or_else(|_| {
+                        eprintln!("Invalid --max-depth: {}", depth);
+                        process::exit(1);
+                    }));
+                }
                 "--brief" => brief = true,
                 "--mime" => mime = true,
+                "--mime-type" => mime_type = true,
+                "--check-extension" => check_ext = true,
+                "--magic-only" => magic_only = true,
+                "--extension-only" => extension_only = true,
+                "--verbose" => verbose = true,
+                "--json" => output = Output::Json,
+                "--ndjson" => output = Output::Ndjson,
                 "--help" => {
                     print_usage_detailed(&args[0]);
                     process::exit(0);
@@ -30,10 +101,29 @@ fn main() {
                 }
             }
         } else if arg.starts_with("-") && arg.len() > 1 {
-            for char in arg.chars().skip(1) {
+            let mut chars = arg[1..].chars();
+            while let Some(char) = chars.next() {
                 match char {
+                    'j' => {
+                        // The count may be attached (`-j4`) or the next argument.
+                        let attached = chars.as_str().to_string();
+                        chars = "".chars();
+                        let count = if attached.is_empty() {
+                            rest.next().cloned().unwrap_or_else(|| {
+                                eprintln!("Option -j requires a value");
+                                process::exit(1);
+                            })
+                        } else {
+                            attached
+                        };
+                        jobs = parse_jobs(&count);
+                    }
                     'b' => brief = true,
                     'i' => mime = true,
+                    'v' => verbose = true,
+                    'r' => recursive = true,
+                    '0' => null_separated = true,
+                    'L' => walk.follow_symlinks = true,
                     'h' => {
                         print_usage_brief(&args[0]);
                         process::exit(0);
@@ -45,73 +135,977 @@ fn main() {
                 }
             }
         } else {
-            files.push(arg);
+            files.push(PathBuf::from(arg));
         }
     }
 
-    if files.is_empty() {
+    if list_formats {
+        // The list describes the built-in database; formats loaded from a
+        // file have no `Format` of their own to list them under.
+        if !signature_files.is_empty() {
+            eprintln!("--list-formats cannot be combined with --signatures");
+            process::exit(1);
+        }
+        print_formats(output);
+        process::exit(0);
+    }
+
+    if files.is_empty() && files_from.is_empty() {
         print_usage_brief(&args[0]);
         process::exit(1);
     }
 
+    if files.iter().any(|file| file.as_os_str() == "-") && files_from.iter().any(|list| list == "-")
+    {
+        eprintln!("Standard input cannot be both a file list and a file to analyze");
+        process::exit(1);
+    }
+
+    for list in &files_from {
+        match read_file_list(list, null_separated) {
+            Ok(paths) => files.extend(paths),
+            Err(err) => {
+                eprintln!("{}: cannot read file list ({})", list, err);
+                process::exit(3);
+            }
+        }
+    }
+
+    let options = match (magic_only, extension_only) {
+        (true, true) => {
+            eprintln!("--magic-only and --extension-only cannot be combined");
+            process::exit(1);
+        }
+        (true, false) => DetectOptions::magic_only(),
+        (false, true) => DetectOptions::extension_only(),
+        (false, false) => DetectOptions::default(),
+    };
+
+    let mut db = SignatureDb::builtin();
+    for file in &signature_files {
+        if let Err(err) = db.load(file) {
+            eprintln!("{}: {}", file, err);
+            process::exit(match err {
+                SignatureDbError::Io(_) => 3,
+                _ => 1,
+            });
+        }
+    }
+    let detector = Detector::new(db).with_options(options);
+
+    if output != Output::Text && (check_ext || verbose) {
+        eprintln!("--json and --ndjson cannot be combined with --check-extension or --verbose");
+        process::exit(1);
+    }
+
+    if summarize && (listing.is_some() || check_ext || verbose) {
+        eprintln!(
+            "--summary cannot be combined with --only-archives, --only-non-archives, --check-extension or --verbose"
+        );
+        process::exit(1);
+    }
+
+    if listing.is_some() && (output != Output::Text || check_ext || verbose) {
+        eprintln!(
+            "--only-archives and --only-non-archives cannot be combined with --json, --ndjson, --check-extension or --verbose"
+        );
+        process::exit(1);
+    }
+
+    let inputs = if recursive {
+        walk.expand(&files)
+    } else {
+        files.into_iter().map(Input::from_arg).collect()
+    };
+
     let mut exit_code = 0;
+    let mut objects = Vec::new();
+    let mut summary = Summary::default();
 
-    for path_str in files {
-        let path = Path::new(path_str);
-        if !path.exists() {
-            eprintln!("{}: No such file or directory", path_str);
-            exit_code = 2;
-            continue;
+    let work = |input: &Input| -> Report {
+        let path = match input {
+            Input::Unreadable(_, err) => {
+                let err = io::Error::new(err.kind(), err.to_string());
+                return Report::Detect(Outcome::Failed(DetectError::Unreadable(err)));
+            }
+            Input::Stdin if check_ext => return Report::Check(check_stdin(&detector)),
+            Input::Stdin => return Report::Detect(analyze_stdin(&detector)),
+            Input::Path(path) => path,
+        };
+        if check_ext {
+            if !path.exists() {
+                return Report::Detect(Outcome::Missing);
+            }
+            return Report::Check(detector.check_extension(path));
         }
+        Report::Detect(analyze_path(path, &detector))
+    };
 
-        let result = analyze(path);
+    let emit = |input: &Input, report: Report| {
+        let name = input.name();
+        let path_str = name.as_ref();
+        let outcome = match report {
+            Report::Check(result) => {
+                let code = report_extension_check(path_str, result, brief);
+                exit_code = cmp::max_by_key(exit_code, code, |&code| severity(code));
+                return;
+            }
+            Report::Detect(outcome) => outcome,
+        };
+        let selected = match &outcome {
+            Outcome::Archive(info) => filter.admits(info.category),
+            _ => false,
+        };
+        // An archive outside the requested categories counts as a non-match.
+        let code = match outcome {
+            Outcome::Archive(_) if !selected => 1,
+            _ => outcome.exit_code(),
+        };
+        exit_code = cmp::max_by_key(exit_code, code, |&code| severity(code));
 
-        match result {
-            Some(info) => {
-                if brief {
-                    println!("{}", info.category);
-                } else if mime {
-                    println!("{}: {}", path_str, info.category);
-                } else {
-                    let prefix = format!("{}: ", path_str);
-                    if !info.hexdump.is_empty() {
-                        println!(
-                            "{}{}\nHex: {}\nType: {}",
-                            prefix, info.description, info.hexdump, info.category
-                        );
-                    } else {
-                        println!("{}{}\nType: {}", prefix, info.description, info.category);
-                    }
+        if summarize {
+            if code > 1 {
+                print_text(path_str, &outcome, brief, mime, mime_type);
+            }
+            summary.add(input, &outcome, selected, code);
+            return;
+        }
+        if let Some(listing) = listing {
+            if code > 1 {
+                print_text(path_str, &outcome, brief, mime, mime_type);
+            } else if selected == (listing == Listing::Archives) {
+                // NUL-terminate with -0 so the list can go straight to `xargs -0`.
+                print_listed(input, if null_separated { b'\0' } else { b'\n' });
+            }
+            return;
+        }
+        if filter.is_active() && code == 1 {
+            return;
+        }
+
+        match output {
+            Output::Text => {
+                print_text(path_str, &outcome, brief, mime, mime_type);
+                if let Input::Path(path) = input
+                    && verbose
+                    && path.exists()
+                    && !check_ext
+                {
+                    print_trace(path, &detector);
                 }
             }
-            None => {
+            Output::Json => objects.push(json_object(path_str, &outcome)),
+            Output::Ndjson => println!("{}", json_object(path_str, &outcome)),
+        }
+    };
+
+    pool::run_pool(&inputs, jobs, work, emit);
+
+    if summarize {
+        match output {
+            Output::Text => summary.print(),
+            Output::Json | Output::Ndjson => println!("{}", summary.to_json()),
+        }
+    } else if output == Output::Json {
+        println!("[{}]", objects.join(",\n"));
+    }
+
+    process::exit(exit_code);
+}
+
+/// Reads the paths listed in `list`, or in standard input for `-`, one per
+/// line or, with `null_separated`, separated by NUL bytes as written by
+/// `find -print0` and `git ls-files -z`. Empty entries are skipped.
+fn read_file_list(list: &str, null_separated: bool) -> io::Result<Vec<PathBuf>> {
+    let mut data = Vec::new();
+    if list == "-" {
+        io::stdin().lock().read_to_end(&mut data)?;
+    } else {
+        fs::File::open(list)?.read_to_end(&mut data)?;
+    }
+
+    let separator = if null_separated { b'\0' } else { b'\n' };
+    Ok(data
+        .split(|&b| b == separator)
+        .map(|entry| {
+            if null_separated {
+                entry
+            } else {
+                entry.strip_suffix(b"\r").unwrap_or(entry)
+            }
+        })
+        .filter(|entry| !entry.is_empty())
+        .map(path_from_bytes)
+        .collect())
+}
+
+/// Paths are arbitrary bytes on Unix. Elsewhere the list has to be UTF-8.
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// The `--category` and `--exclude-category` filters. A file matches when it
+/// is an archive whose category is included (or no category was named) and
+/// not excluded.
+#[derive(Default)]
+struct CategoryFilter {
+    include: Vec<&'static str>,
+    exclude: Vec<&'static str>,
+}
+
+impl CategoryFilter {
+    fn is_active(&self) -> bool {
+        !self.include.is_empty() || !self.exclude.is_empty()
+    }
+
+    fn admits(&self, category: &str) -> bool {
+        (self.include.is_empty() || self.include.contains(&category))
+            && !self.exclude.contains(&category)
+    }
+}
+
+/// Resolves a category given in full (`archive/package`) or by its last part
+/// (`package`).
+fn parse_category(name: &str) -> &'static str {
+    CATEGORIES
+        .iter()
+        .copied()
+        .find(|category| *category == name || category.strip_prefix("archive/") == Some(name))
+        .unwrap_or_else(|| {
+            eprintln!(
+                "Unknown category: {} (expected one of {})",
+                name,
+                CATEGORIES.join(", ")
+            );
+            process::exit(1);
+        })
+}
+
+/// Parses a `-j` worker count, where `0` means one worker per CPU.
+fn parse_jobs(count: &str) -> usize {
+    match count.parse::<usize>() {
+        Ok(jobs) => jobs,
+        Err(_) => {
+            eprintln!("Invalid job count: {}", count);
+            process::exit(1);
+        }
+    }
+}
+
+/// A file to analyze, standard input, or a directory entry the recursive walk
+/// could not read.
+enum Input {
+    Path(PathBuf),
+    Stdin,
+    Unreadable(PathBuf, io::Error),
+}
+
+impl Input {
+    /// Treats `-` as standard input, like `file -`.
+    fn from_arg(arg: PathBuf) -> Self {
+        if arg.as_os_str() == "-" {
+            Input::Stdin
+        } else {
+            Input::Path(arg)
+        }
+    }
+
+    /// The name to report the input under. Paths that are not valid UTF-8
+    /// are shown lossily.
+    fn name(&self) -> Cow<'_, str> {
+        match self {
+            Input::Path(path) | Input::Unreadable(path, _) => path.to_string_lossy(),
+            Input::Stdin => Cow::Borrowed("(standard input)"),
+        }
+    }
+}
+
+/// Prints an input for `--only-archives` and `--only-non-archives`. Paths are
+/// written as their raw bytes on Unix so that the list can be fed back to
+/// other tools.
+fn print_listed(input: &Input, terminator: u8) {
+    let mut stdout = io::stdout().lock();
+    let written = match input {
+        #[cfg(unix)]
+        Input::Path(path) => {
+            use std::os::unix::ffi::OsStrExt;
+            stdout.write_all(path.as_os_str().as_bytes())
+        }
+        _ => stdout.write_all(input.name().as_bytes()),
+    };
+    if written
+        .and_then(|()| stdout.write_all(&[terminator]))
+        .is_err()
+    {
+        process::exit(1);
+    }
+}
+
+/// The result of the per-file work done by a worker thread.
+enum Report {
+    Detect(Outcome),
+    Check(Result<ExtensionCheck, DetectError>),
+}
+
+/// Settings for `--recursive` directory scanning.
+#[derive(Default)]
+struct Walk {
+    follow_symlinks: bool,
+    /// Include entries whose names start with a `.`.
+    hidden: bool,
+    /// Only analyze files whose name matches one of these globs.
+    include: Vec<String>,
+    /// Skip files and directories whose name matches one of these globs.
+    exclude: Vec<String>,
+    /// How many directory levels below each argument to descend.
+    max_depth: Option<usize>,
+}
+
+impl Walk {
+    /// Replaces directory arguments with the files beneath them, in sorted
+    /// order. Paths named on the command line are always kept.
+    fn expand(&self, args: &[PathBuf]) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        let mut visited = HashSet::new();
+        for arg in args {
+            let path = arg.as_path();
+            if path.is_dir() {
+                if let Ok(real) = fs::canonicalize(path) {
+                    visited.insert(real);
+                }
+                self.visit(path, 1, &mut visited, &mut inputs);
+            } else {
+                inputs.push(Input::from_arg(arg.clone()));
+            }
+        }
+        inputs
+    }
+
+    fn visit(
+        &self,
+        dir: &Path,
+        depth: usize,
+        visited: &mut HashSet<PathBuf>,
+        inputs: &mut Vec<Input>,
+    ) {
+        if self.max_depth.is_some_and(|max| depth > max) {
+            return;
+        }
+        let entries =
+            match fs::read_dir(dir).and_then(|entries| entries.collect::<io::Result<Vec<_>>>()) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    inputs.push(Input::Unreadable(dir.to_path_buf(), err));
+                    return;
+                }
+            };
+        let mut paths: Vec<PathBuf> = entries.iter().map(|entry| entry.path()).collect();
+        paths.sort();
+
+        for path in paths {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy())
+                .unwrap_or_default();
+            if !self.hidden && name.starts_with('.') {
+                continue;
+            }
+            if self.exclude.iter().any(|glob| glob_match(glob, &name)) {
+                continue;
+            }
+
+            let is_symlink = fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_symlink());
+            if is_symlink && !self.follow_symlinks {
+                continue;
+            }
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    inputs.push(Input::Unreadable(path.clone(), err));
+                    continue;
+                }
+            };
+
+            if metadata.is_dir() {
+                // Following symlinks can lead back to a directory already seen.
+                let first_visit = fs::canonicalize(&path).map_or(true, |real| visited.insert(real));
+                if first_visit {
+                    self.visit(&path, depth + 1, visited, inputs);
+                }
+            } else if self.include.is_empty()
+                || self.include.iter().any(|glob| glob_match(glob, &name))
+            {
+                inputs.push(Input::Path(path));
+            }
+        }
+    }
+}
+
+/// Matches a file name against a shell-style glob where `*` matches any run
+/// of characters and `?` any single character.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match (pattern.first(), name.first()) {
+            (None, None) => true,
+            (Some('*'), _) => {
+                matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..]))
+            }
+            (Some('?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+            (Some(p), Some(c)) => p == c && matches(&pattern[1..], &name[1..]),
+            _ => false,
+        }
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches(&pattern, &name)
+}
+
+/// How results are printed.
+#[derive(Clone, Copy, PartialEq)]
+enum Output {
+    Text,
+    /// A single JSON array once every file is analyzed.
+    Json,
+    /// One JSON object per line as each file is analyzed.
+    Ndjson,
+}
+
+/// Which paths `--only-archives` and `--only-non-archives` print.
+#[derive(Clone, Copy, PartialEq)]
+enum Listing {
+    Archives,
+    NonArchives,
+}
+
+/// What happened to one input file.
+enum Outcome {
+    Archive(ArchiveInfo),
+    NotArchive,
+    Missing,
+    Failed(DetectError),
+}
+
+impl Outcome {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Outcome::Archive(_) => 0,
+            Outcome::NotArchive | Outcome::Failed(DetectError::TooShort(_)) => 1,
+            Outcome::Missing => 2,
+            Outcome::Failed(_) => 3,
+        }
+    }
+
+    fn error(&self) -> Option<String> {
+        match self {
+            Outcome::Missing => Some("No such file or directory".to_string()),
+            Outcome::Failed(err) => Some(err.to_string()),
+            _ => None,
+        }
+    }
+}
+
+fn analyze_path(path: &Path, detector: &Detector) -> Outcome {
+    if !path.exists() {
+        return Outcome::Missing;
+    }
+    match detector.try_analyze(path) {
+        Ok(Some(info)) => Outcome::Archive(info),
+        Ok(None) => Outcome::NotArchive,
+        Err(err) => Outcome::Failed(err),
+    }
+}
+
+/// Analyzes standard input. It has no name, so only magic detection applies.
+fn analyze_stdin(detector: &Detector) -> Outcome {
+    if !detector.options().magic {
+        return Outcome::NotArchive;
+    }
+    match detector.analyze_stream(io::stdin().lock(), None) {
+        Ok(Some(info)) => Outcome::Archive(info),
+        Ok(None) => Outcome::NotArchive,
+        Err(err) => Outcome::Failed(err),
+    }
+}
+
+/// Standard input has no extension to compare with, so its content can at
+/// best be recognized on its own.
+fn check_stdin(detector: &Detector) -> Result<ExtensionCheck, DetectError> {
+    // Like `check_extension`, the content is checked whatever the options.
+    let detector = Detector::new(detector.db().clone());
+    match detector.analyze_stream(io::stdin().lock(), None) {
+        Ok(Some(info)) => Ok(ExtensionCheck::MagicOnly(info)),
+        Ok(None) | Err(DetectError::TooShort(_)) => Ok(ExtensionCheck::Neither),
+        Err(err) => Err(err),
+    }
+}
+
+fn print_text(path_str: &str, outcome: &Outcome, brief: bool, mime: bool, mime_type: bool) {
+    match outcome {
+        Outcome::Archive(info) => {
+            if mime_type {
+                let media_type = info.mime_type.unwrap_or("application/octet-stream");
                 if brief {
-                    println!("not an archive");
+                    println!("{}", media_type);
                 } else {
-                    println!("{}: not an archive", path_str);
+                    println!("{}: {}", path_str, media_type);
                 }
-                if exit_code == 0 {
-                    exit_code = 1;
+            } else if brief {
+                println!("{}", info.category);
+            } else if mime {
+                println!("{}: {}", path_str, info.category);
+            } else {
+                let prefix = format!("{}: ", path_str);
+                if !info.hexdump.is_empty() {
+                    println!(
+                        "{}{}\nHex: {}\nType: {}",
+                        prefix, info.description, info.hexdump, info.category
+                    );
+                } else {
+                    println!("{}{}\nType: {}", prefix, info.description, info.category);
                 }
             }
         }
+        Outcome::NotArchive => {
+            if brief {
+                println!("not an archive");
+            } else {
+                println!("{}: not an archive", path_str);
+            }
+        }
+        Outcome::Failed(err @ DetectError::TooShort(_)) => {
+            if brief {
+                println!("{}", err);
+            } else {
+                println!("{}: {}", path_str, err);
+            }
+        }
+        Outcome::Missing | Outcome::Failed(_) => {
+            eprintln!("{}: {}", path_str, outcome.error().unwrap_or_default());
+        }
     }
+}
 
-    process::exit(exit_code);
+/// Formats one file's outcome as a JSON object.
+///
+/// Detection fields are `null` when nothing was detected; `error` is `null`
+/// unless the file was missing, unreadable or too short.
+fn json_object(path_str: &str, outcome: &Outcome) -> String {
+    let mut fields = vec![
+        ("path", json_string(path_str)),
+        (
+            "detected",
+            matches!(outcome, Outcome::Archive(_)).to_string(),
+        ),
+    ];
+    match outcome {
+        Outcome::Archive(info) => {
+            let extensions: Vec<String> = info.extensions.iter().map(|e| json_string(e)).collect();
+            fields.extend([
+                ("format", json_string(info.format.id())),
+                ("category", json_string(info.category)),
+                (
+                    "mime_type",
+                    info.mime_type.map_or("null".to_string(), json_string),
+                ),
+                ("description", json_string(info.description)),
+                ("hexdump", json_string(info.hexdump)),
+                ("extension", json_string(info.extension)),
+                ("extensions", format!("[{}]", extensions.join(","))),
+                ("confidence", info.confidence.to_string()),
+                (
+                    "offset",
+                    info.offset.map_or("null".to_string(), |o| o.to_string()),
+                ),
+                ("method", json_string(info.method().as_str())),
+            ]);
+        }
+        _ => {
+            for key in [
+                "format",
+                "category",
+                "mime_type",
+                "description",
+                "hexdump",
+                "extension",
+                "extensions",
+                "confidence",
+                "offset",
+                "method",
+            ] {
+                fields.push((key, "null".to_string()));
+            }
+        }
+    }
+    fields.push((
+        "error",
+        outcome
+            .error()
+            .map_or("null".to_string(), |e| json_string(&e)),
+    ));
+
+    let members: Vec<String> = fields
+        .into_iter()
+        .map(|(key, value)| format!("\"{}\":{}", key, value))
+        .collect();
+    format!("{{{}}}", members.join(","))
+}
+
+/// Prints every supported format for `--list-formats`, as a table or, with
+/// `--json` or `--ndjson`, as JSON.
+fn print_formats(output: Output) {
+    let formats: Vec<&FormatInfo> = supported_formats().collect();
+    let object = |info: &FormatInfo| {
+        let extensions: Vec<String> = info.extensions.iter().map(|e| json_string(e)).collect();
+        format!(
+            "{{\"format\":{},\"extension\":{},\"extensions\":[{}],\"category\":{},\"mime_type\":{},\"description\":{},\"signatures\":{}}}",
+            json_string(info.format.id()),
+            json_string(info.extension),
+            extensions.join(","),
+            json_string(info.category),
+            info.mime_type.map_or("null".to_string(), json_string),
+            json_string(info.description),
+            info.signatures
+        )
+    };
+
+    match output {
+        Output::Json => {
+            let objects: Vec<String> = formats.iter().map(|info| object(info)).collect();
+            println!("[{}]", objects.join(",\n"));
+        }
+        Output::Ndjson => {
+            for info in formats {
+                println!("{}", object(info));
+            }
+        }
+        Output::Text => {
+            let id_width = formats
+                .iter()
+                .map(|f| f.format.id().len())
+                .max()
+                .unwrap_or(0);
+            let ext_width = formats.iter().map(|f| f.extension.len()).max().unwrap_or(0);
+            println!(
+                "{:<id_width$}  {:<ext_width$}  {:<26}  {:>10}  DESCRIPTION",
+                "FORMAT", "EXTENSION", "CATEGORY", "SIGNATURES"
+            );
+            for info in formats {
+                println!(
+                    "{:<id_width$}  {:<ext_width$}  {:<26}  {:>10}  {}",
+                    info.format.id(),
+                    info.extension,
+                    info.category,
+                    info.signatures,
+                    info.description
+                );
+            }
+        }
+    }
+}
+
+/// Totals collected over a scan for `--summary`.
+#[derive(Default)]
+struct Summary {
+    files: usize,
+    archives: usize,
+    not_archives: usize,
+    errors: usize,
+    /// File count and total size in bytes per category.
+    categories: BTreeMap<&'static str, (usize, u64)>,
+    formats: BTreeMap<&'static str, usize>,
+    /// Archives recognized only by their extension, in scan order.
+    extension_only: Vec<String>,
+}
+
+impl Summary {
+    fn add(&mut self, input: &Input, outcome: &Outcome, selected: bool, code: i32) {
+        self.files += 1;
+        match outcome {
+            Outcome::Archive(info) if selected => {
+                self.archives += 1;
+                // Standard input has no size to report.
+                let bytes = match input {
+                    Input::Path(path) => fs::metadata(path).map_or(0, |m| m.len()),
+                    _ => 0,
+                };
+                let category = self.categories.entry(info.category).or_default();
+                category.0 += 1;
+                category.1 += bytes;
+                *self.formats.entry(info.format.id()).or_default() += 1;
+                if info.method() == Method::Extension {
+                    self.extension_only.push(input.name().into_owned());
+                }
+            }
+            _ if code > 1 => self.errors += 1,
+            _ => self.not_archives += 1,
+        }
+    }
+
+    fn print(&self) {
+        println!(
+            "{} files: {} archives, {} not archives, {} errors",
+            self.files, self.archives, self.not_archives, self.errors
+        );
+        if !self.categories.is_empty() {
+            println!();
+            println!("By category:");
+            for (category, (files, bytes)) in &self.categories {
+                println!("  {:<28} {:>6} files {:>14} bytes", category, files, bytes);
+            }
+        }
+        if !self.formats.is_empty() {
+            println!();
+            println!("By format:");
+            for (format, files) in &self.formats {
+                println!("  {:<28} {:>6} files", format, files);
+            }
+        }
+        if !self.extension_only.is_empty() {
+            println!();
+            println!("Detected by extension only:");
+            for path in &self.extension_only {
+                println!("  {}", path);
+            }
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let categories: Vec<String> = self
+            .categories
+            .iter()
+            .map(|(category, (files, bytes))| {
+                format!(
+                    "{}:{{\"files\":{},\"bytes\":{}}}",
+                    json_string(category),
+                    files,
+                    bytes
+                )
+            })
+            .collect();
+        let formats: Vec<String> = self
+            .formats
+            .iter()
+            .map(|(format, files)| format!("{}:{}", json_string(format), files))
+            .collect();
+        let extension_only: Vec<String> =
+            self.extension_only.iter().map(|p| json_string(p)).collect();
+        format!(
+            "{{\"files\":{},\"archives\":{},\"not_archives\":{},\"errors\":{},\"categories\":{{{}}},\"formats\":{{{}}},\"extension_only\":[{}]}}",
+            self.files,
+            self.archives,
+            self.not_archives,
+            self.errors,
+            categories.join(","),
+            formats.join(","),
+            extension_only.join(",")
+        )
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Prints how `--verbose` reached its result: every signature that matched
+/// and each extension suffix looked up.
+fn print_trace(path: &Path, detector: &Detector) {
+    let Ok(trace) = detector.explain(path) else {
+        return;
+    };
+    let options = detector.options();
+
+    if options.magic && trace.candidates.is_empty() {
+        println!("  signatures: none matched");
+    }
+    for candidate in &trace.candidates {
+        let info = &candidate.info;
+        let verdict = match candidate.verdict {
+            Verdict::Selected => "selected",
+            Verdict::Outranked => "outranked",
+            Verdict::MissingExtension => "rejected, file name lacks the extension",
+        };
+        println!(
+            "  signature {} at offset {} [{} {}]: {} ({})",
+            info.hexdump,
+            info.offset.unwrap_or(0),
+            info.category,
+            info.extension,
+            info.description,
+            verdict
+        );
+    }
+
+    if options.extension && trace.suffixes.is_empty() && !trace.candidates.is_empty() {
+        println!("  extensions: not consulted, a signature matched");
+    } else if options.extension && trace.suffixes.is_empty() {
+        println!("  extensions: file name has none");
+    }
+    for lookup in &trace.suffixes {
+        match &lookup.info {
+            Some(info) => println!(
+                "  extension {}: {} [{} {}]",
+                lookup.suffix, info.description, info.category, info.extension
+            ),
+            None => println!("  extension {}: no match", lookup.suffix),
+        }
+    }
+}
+
+/// Ranks exit codes so that the most severe one across all files wins.
+/// Errors (2 and 3) outrank the findings of `--check-extension` (4 and 5), so
+/// that a file that could not be checked is never reported as a mismatch.
+fn severity(code: i32) -> i32 {
+    match code {
+        2 | 3 => code + 4,
+        _ => code,
+    }
+}
+
+/// Prints the outcome of `--check-extension` and returns its exit code.
+fn report_extension_check(
+    path_str: &str,
+    result: Result<ExtensionCheck, DetectError>,
+    brief: bool,
+) -> i32 {
+    let (status, detail, code) = match result {
+        Ok(ExtensionCheck::Match { magic, .. }) => ("match", magic.description.to_string(), 0),
+        Ok(ExtensionCheck::Mismatch { magic, extension }) => (
+            "mismatch",
+            format!(
+                "content is {} ({}), name suggests {} ({})",
+                magic.description,
+                magic.canonical_extension(),
+                extension.description,
+                extension.extension
+            ),
+            5,
+        ),
+        Ok(ExtensionCheck::MagicOnly(magic)) => (
+            "magic only",
+            format!(
+                "content is {} ({})",
+                magic.description,
+                magic.canonical_extension()
+            ),
+            4,
+        ),
+        Ok(ExtensionCheck::ExtensionOnly(extension)) => (
+            "extension only",
+            format!(
+                "content not recognized, name suggests {} ({})",
+                extension.description, extension.extension
+            ),
+            4,
+        ),
+        Ok(ExtensionCheck::Neither) => ("not an archive", String::new(), 1),
+        Err(err) => {
+            eprintln!("{}: {}", path_str, err);
+            return 3;
+        }
+    };
+
+    if brief {
+        println!("{}", status);
+    } else if detail.is_empty() {
+        println!("{}: {}", path_str, status);
+    } else {
+        println!("{}: {}: {}", path_str, status, detail);
+    }
+    code
 }
 
 fn print_usage_brief(prog_name: &str) {
-    println!("Usage: {} [-b] [-i] <file_path>...", prog_name);
+    println!(
+        "Usage: {} [-b] [-i] [-v] [-r] [--files-from FILE] <file_path>...",
+        prog_name
+    );
 }
 
 fn print_usage_detailed(prog_name: &str) {
     println!("Usage: {} [OPTIONS] <file_path>...", prog_name);
     println!();
     println!("Detect archive types based on magic signatures and extensions.");
+    println!("A file_path of '-' reads the data from standard input.");
     println!();
     println!("Options:");
     println!("  -b, --brief    Print brief output (category only, no filename).");
     println!("  -i, --mime     Print MIME type/category (e.g., archive/storage).");
+    println!("  -v, --verbose  Explain which signatures and extensions were considered.");
+    println!("  --mime-type    Print the IANA media type (e.g., application/zip).");
+    println!("  --magic-only   Identify files by content only, ignoring extensions.");
+    println!("  --extension-only");
+    println!("                 Identify files by extension only, without reading them.");
+    println!("  --check-extension");
+    println!("                 Check that each file's extension agrees with its content.");
+    println!("  -r, --recursive");
+    println!("                 Analyze every file beneath directory arguments.");
+    println!("  -L, --follow-symlinks");
+    println!("                 Follow symbolic links while recursing (skipped by default).");
+    println!("  --hidden       Include hidden files and directories while recursing.");
+    println!("  --include GLOB Only analyze files whose name matches GLOB (repeatable).");
+    println!("  --exclude GLOB Skip files and directories whose name matches GLOB (repeatable).");
+    println!("  --max-depth N  Descend at most N directory levels below each argument.");
+    println!("  -j, --jobs N   Analyze N files at a time (0 = one per CPU). Output order");
+    println!("                 is unchanged.");
+    println!("  --files-from FILE");
+    println!(
+        "                 Also analyze the paths listed in FILE, one per line ('-' for stdin)."
+    );
+    println!("  -0, --null     Paths in --files-from lists are separated by NUL bytes, and");
+    println!("                 so are the paths printed by --only-archives.");
+    println!("  --category CAT Only report archives in category CAT, e.g. archive/package");
+    println!("                 or just package (repeatable).");
+    println!("  --exclude-category CAT");
+    println!("                 Do not report archives in category CAT (repeatable).");
+    println!("  --only-archives");
+    println!("                 Print just the paths of files that match, e.g. for xargs.");
+    println!("  --only-non-archives");
+    println!("                 Print just the paths of files that do not match.");
+    println!("  --summary      Print counts per category and format, bytes per category and");
+    println!("                 the files detected by extension only, instead of each file.");
+    println!("  --signatures FILE");
+    println!("                 Also use the signatures in FILE, a YAML file in the format of");
+    println!("                 archive_signatures.yaml (repeatable).");
+    println!("  --list-formats Print every built-in format with its extension, category");
+    println!("                 and number of signatures, then exit.");
+    println!("  --json         Print results as a JSON array.");
+    println!("  --ndjson       Print results as one JSON object per line.");
     println!("  -h             Print brief usage.");
     println!("  --help         Print this detailed help message.");
+    println!();
+    println!("Exit status:");
+    println!("  0  every file is an archive");
+    println!("  1  at least one file is not an archive");
+    println!("  2  at least one file does not exist");
+    println!("  3  at least one file could not be read");
+    println!();
+    println!("With --check-extension, exit status is the first that applies of:");
+    println!("  3  at least one file could not be read");
+    println!("  2  at least one file does not exist");
+    println!("  5  content and extension identify different formats");
+    println!("  4  only one of content and extension is recognized");
+    println!("  1  neither content nor extension is recognized");
+    println!("  0  content and extension agree");
 }
@@ -0,0 +1,65 @@
+//! The worker pool behind `Detector::analyze_many`. The command-line tool
+//! includes this module as well, so that `--jobs` runs on the same pool.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// Runs `work` on every item using up to `jobs` threads and hands each result
+/// to `emit` on the calling thread, in item order.
+///
+/// `0` uses one thread per available CPU and `1` does the work sequentially
+/// on the calling thread.
+pub(crate) fn run_pool<T, R, W, E>(items: &[T], jobs: usize, work: W, mut emit: E)
+where
+    T: Sync,
+    R: Send,
+    W: Fn(&T) -> R + Sync,
+    E: FnMut(&T, R),
+{
+    let jobs = match jobs {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        jobs => jobs,
+    };
+    if jobs == 1 {
+        for item in items {
+            emit(item, work(item));
+        }
+        return;
+    }
+
+    // Workers claim the next unprocessed index until none are left.
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(items.len()) {
+            let sender = sender.clone();
+            let (next, work) = (&next, &work);
+            scope.spawn(move || {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    if sender.send((index, work(item))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Results arrive in completion order; hold them back until every
+        // earlier item has been emitted.
+        let mut pending = BTreeMap::new();
+        let mut expected = 0;
+        for (index, result) in receiver {
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&expected) {
+                emit(&items[expected], result);
+                expected += 1;
+            }
+        }
+    });
+}
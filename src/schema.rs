@@ -0,0 +1,767 @@
+//! Parser for the `archive_signatures.yaml` schema.
+//!
+//! Shared by `build.rs`, which compiles the bundled database into the crate,
+//! and [`SignatureDb`](crate::SignatureDb), which loads further files at
+//! runtime. It only depends on `std` so that the build script can include it
+//! with `#[path]`.
+
+use std::collections::{BTreeMap, HashMap};
+
+/// YAML section names and the categories their entries are filed under.
+pub(crate) const SECTIONS: &[(&str, &str)] = &[
+    ("archiveOnly", "archive/storage"),
+    ("compressOnly", "archive/stream-compression"),
+    ("archiveAndCompress", "archive/compressed-archive"),
+    ("dataRecovery", "archive/recovery"),
+    ("packaging", "archive/package"),
+    ("containers", "archive/container"),
+];
+
+/// How far into a file, or back from its end, a signature may look. Readers
+/// buffer up to this much of a stream, so it has to stay small.
+pub(crate) const MAX_REACH: usize = 16 << 20;
+
+/// Which category an extension listed in several sections reports, most
+/// preferred first.
+const CATEGORY_PRIORITY: &[&str] = &[
+    "archive/package",
+    "archive/recovery",
+    "archive/compressed-archive",
+    "archive/stream-compression",
+    "archive/storage",
+    "archive/container",
+];
+
+#[derive(Clone, PartialEq)]
+pub(crate) struct PatternData {
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) mask: Option<Vec<u8>>,
+    pub(crate) offset: usize,
+}
+
+#[derive(Clone)]
+pub(crate) struct SigData {
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) mask: Option<Vec<u8>>,
+    pub(crate) offset: usize,
+    pub(crate) from_end: bool,
+    pub(crate) search: usize,
+    pub(crate) and: Vec<PatternData>,
+    pub(crate) extension_required: bool,
+    /// Format id, from the signature's `format` field or else its entry's.
+    pub(crate) format: String,
+    /// IANA media type from the signature's `mime_type` field.
+    pub(crate) mime_type: Option<String>,
+    pub(crate) description: String,
+    pub(crate) hexdump_str: String,
+}
+
+impl SigData {
+    /// Whether two signatures look for the same bytes in the same place,
+    /// regardless of which entry they were filed under.
+    pub(crate) fn same_pattern(&self, other: &SigData) -> bool {
+        self.bytes == other.bytes
+            && self.mask == other.mask
+            && self.offset == other.offset
+            && self.from_end == other.from_end
+            && self.search == other.search
+            && self.and == other.and
+    }
+}
+
+pub(crate) struct Entry {
+    pub(crate) ext: String,
+    pub(crate) category_mime: String,
+    /// Line the extension key first appeared on, for error messages.
+    line: usize,
+    /// Format id from the entry's `format` field, else derived from the key.
+    /// Empty if the key gives no valid id.
+    pub(crate) format: String,
+    /// IANA media type from the entry's `mime_type` field.
+    pub(crate) mime_type: Option<String>,
+    /// The entry's own `description`, for keys without signatures.
+    pub(crate) description: Option<String>,
+    pub(crate) signatures: Vec<SigData>,
+}
+
+#[derive(Default)]
+struct PendingPattern {
+    bytes: Option<Vec<u8>>,
+    wildcards: Vec<u8>,
+    mask: Option<Vec<u8>>,
+    offset: usize,
+}
+
+struct PendingSig {
+    /// Line the signature's list item starts on, for error messages.
+    line: usize,
+    /// Column the signature's keys start at. A less indented line ends it.
+    column: usize,
+    pattern: PendingPattern,
+    from_end: bool,
+    search: usize,
+    and: Vec<PendingPattern>,
+    /// Whether the signature's `and:` list has started.
+    has_and: bool,
+    /// Column the keys of the current `and:` item start at, while in one.
+    and_column: Option<usize>,
+    extension_required: bool,
+    format: Option<String>,
+    mime_type: Option<String>,
+    description: Option<String>,
+    hexdump_str: String,
+}
+
+impl PendingSig {
+    fn new(line: usize, column: usize) -> Self {
+        PendingSig {
+            line,
+            column,
+            pattern: PendingPattern::default(),
+            from_end: false,
+            search: 0,
+            and: Vec::new(),
+            has_and: false,
+            and_column: None,
+            extension_required: false,
+            format: None,
+            mime_type: None,
+            description: None,
+            hexdump_str: String::new(),
+        }
+    }
+}
+
+/// Parses the signature database in `content`.
+///
+/// Anything malformed is an error rather than skipped, so that a typo cannot
+/// silently change a signature. Errors name the offending line.
+///
+/// Only the subset of YAML the bundled database uses is accepted: block
+/// mappings and lists, `signatures: []`, full-line `#` comments, and scalars
+/// that are double-quoted strings without escapes, decimal integers, `true`,
+/// `false`, format ids or `[start, end]` ranges. Indentation decides where a
+/// signature and its `and:` items end.
+pub(crate) fn parse(content: &str) -> Result<Vec<Entry>, String> {
+    let mut entries: Vec<Entry> = Vec::new();
+
+    let mut current_category_mime = String::new();
+    let mut current_ext = String::new();
+    // Whether the current extension's `signatures` list has started.
+    let mut in_signatures = false;
+
+    let mut current_sig: Option<PendingSig> = None;
+
+    for (index, raw) in content.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let at_line = |message: String| format!("line {}: {}", index + 1, message);
+
+        // The keys of a `- key: value` list item line up after the dash.
+        let (item, body) = match line.strip_prefix('-') {
+            Some(rest) if rest.starts_with(' ') => (true, rest.trim_start()),
+            _ => (false, line),
+        };
+        let column = raw.len() - raw.trim_start().len() + line.len() - body.len();
+        let Some((key, value)) = body.split_once(':') else {
+            return Err(at_line(format!(
+                "Expected \"key: value\", found {:?}",
+                line
+            )));
+        };
+        let value = value.trim();
+
+        if let Some(s) = current_sig.as_mut()
+            && s.and_column.is_some_and(|and_column| column < and_column)
+        {
+            s.and_column = None;
+        }
+        if let Some(s) = current_sig.take_if(|s| column < s.column) {
+            push_signature(&mut entries, &current_ext, &current_category_mime, s)?;
+        }
+
+        if item {
+            match current_sig.as_mut() {
+                Some(s) if s.has_and && column > s.column => {
+                    s.and.push(PendingPattern::default());
+                    s.and_column = Some(column);
+                }
+                _ if in_signatures => {
+                    if let Some(s) = current_sig.take() {
+                        push_signature(&mut entries, &current_ext, &current_category_mime, s)?;
+                    }
+                    current_sig = Some(PendingSig::new(index + 1, column));
+                }
+                _ => {
+                    return Err(at_line(
+                        "list item outside a signatures or and: list".to_string(),
+                    ));
+                }
+            }
+        } else if key == "signatures" && (value.is_empty() || value == "[]") {
+            if current_ext.is_empty() || current_sig.is_some() || in_signatures {
+                return Err(at_line(
+                    "signatures must be directly inside an extension".to_string(),
+                ));
+            }
+            in_signatures = true;
+            continue;
+        } else if key == "and" && value.is_empty() {
+            match current_sig.as_mut() {
+                Some(s) if !s.has_and => s.has_and = true,
+                _ => return Err(at_line("and: must be inside a signature".to_string())),
+            }
+            continue;
+        } else if value.is_empty() {
+            if let Some(s) = current_sig.take() {
+                push_signature(&mut entries, &current_ext, &current_category_mime, s)?;
+            }
+            in_signatures = false;
+            current_ext.clear();
+
+            if column == 0 && key.starts_with('.') {
+                return Err(at_line(format!(
+                    "extension {} is not inside a category section",
+                    key
+                )));
+            } else if column == 0 {
+                let Some((_, mime)) = SECTIONS.iter().find(|(section, _)| *section == key) else {
+                    return Err(at_line(format!("Unknown section {:?}", key)));
+                };
+                current_category_mime = mime.to_string();
+            } else if key.starts_with('.') && !current_category_mime.is_empty() {
+                current_ext = key.to_string();
+                entry_mut(
+                    &mut entries,
+                    &current_ext,
+                    &current_category_mime,
+                    index + 1,
+                );
+            } else {
+                return Err(at_line(format!("Unknown key {:?}", key)));
+            }
+            continue;
+        }
+
+        match current_sig.as_mut() {
+            Some(s) if s.and_column.is_some() => set_and_field(s, key, value),
+            Some(s) => set_signature_field(s, key, value),
+            None if current_ext.is_empty() => Err(format!("{} is not inside an extension", key)),
+            None if in_signatures => Err(format!(
+                "{} must come before the extension's signatures",
+                key
+            )),
+            None => set_extension_field(
+                entry_mut(
+                    &mut entries,
+                    &current_ext,
+                    &current_category_mime,
+                    index + 1,
+                ),
+                key,
+                value,
+            ),
+        }
+        .map_err(at_line)?;
+    }
+    // Push final
+    if let Some(s) = current_sig.take() {
+        push_signature(&mut entries, &current_ext, &current_category_mime, s)?;
+    }
+
+    for (i, entry) in entries.iter().enumerate() {
+        let at_line = |message: String| format!("line {}: {}", entry.line, message);
+        if entry.format.is_empty() {
+            return Err(at_line(format!(
+                "extension {} needs an explicit format id",
+                entry.ext
+            )));
+        }
+        if let Some(other) = entries[..i].iter().find(|other| {
+            other.ext.eq_ignore_ascii_case(&entry.ext) && other.format != entry.format
+        }) {
+            return Err(at_line(format!(
+                "Conflicting format for {}: {} and {}",
+                entry.ext, other.format, entry.format
+            )));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Parses space separated hex bytes, failing on anything else.
+///
+/// With `wildcards`, a `??` token matches any byte and parses as `(0, 0x00)`;
+/// every other token parses as `(byte, 0xFF)`.
+fn parse_hex(field: &str, text: &str, wildcards: bool) -> Result<Vec<(u8, u8)>, String> {
+    text.split_whitespace()
+        .map(|token| {
+            if wildcards && token == "??" {
+                return Ok((0, 0x00));
+            }
+            match u8::from_str_radix(token, 16) {
+                Ok(byte) if token.len() == 2 => Ok((byte, 0xFF)),
+                _ => Err(format!(
+                    "Invalid {} token {:?} in \"{}\"",
+                    field, token, text
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Applies an extension's `key: value` line.
+fn set_extension_field(entry: &mut Entry, key: &str, value: &str) -> Result<(), String> {
+    match key {
+        "mime_type" => entry.mime_type = Some(string(key, value)?.to_string()),
+        "description" => entry.description = Some(string(key, value)?.to_string()),
+        "format" => entry.format = format_id(value)?,
+        _ => return Err(format!("Unknown extension key {:?}", key)),
+    }
+    Ok(())
+}
+
+/// Applies a signature's `key: value` line.
+fn set_signature_field(s: &mut PendingSig, key: &str, value: &str) -> Result<(), String> {
+    match key {
+        "description" => s.description = Some(string(key, value)?.to_string()),
+        "format" => s.format = Some(format_id(value)?),
+        "mime_type" => s.mime_type = Some(string(key, value)?.to_string()),
+        "hexdump" => {
+            set_hexdump(&mut s.pattern, value)?;
+            s.hexdump_str = string(key, value)?.to_string();
+        }
+        "mask" => set_mask(&mut s.pattern, value)?,
+        "offset" => {
+            // Negative offsets count back from the end of the file.
+            let offset: i64 = integer(key, value)?;
+            s.pattern.offset = offset.unsigned_abs() as usize;
+            s.from_end = offset < 0;
+        }
+        "offset_range" => {
+            let invalid = || format!("Invalid offset_range {:?}, expected [start, end]", value);
+            let bounds = value
+                .strip_prefix('[')
+                .and_then(|range| range.strip_suffix(']'))
+                .ok_or_else(invalid)?
+                .split(',')
+                .map(|bound| bound.trim().parse().map_err(|_| invalid()))
+                .collect::<Result<Vec<usize>, _>>()?;
+            match bounds[..] {
+                [start, end] if start <= end => {
+                    // The marker may start anywhere in start..=end.
+                    s.pattern.offset = start;
+                    s.search = end - start;
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        "extension_required" => {
+            s.extension_required = match value {
+                "true" => true,
+                "false" => false,
+                _ => {
+                    return Err(format!(
+                        "Invalid extension_required {:?}, expected true or false",
+                        value
+                    ));
+                }
+            }
+        }
+        _ => return Err(format!("Unknown signature key {:?}", key)),
+    }
+    Ok(())
+}
+
+/// Applies a `key: value` line of a signature's current `and:` item.
+fn set_and_field(s: &mut PendingSig, key: &str, value: &str) -> Result<(), String> {
+    let pattern = s
+        .and
+        .last_mut()
+        .expect("an and: item is open only after one was pushed");
+    match key {
+        "hexdump" => set_hexdump(pattern, value),
+        "mask" => set_mask(pattern, value),
+        "offset" => {
+            // `and:` offsets are relative to where the primary bytes matched.
+            pattern.offset = usize::try_from(integer::<i64>(key, value)?)
+                .map_err(|_| format!("Negative offset {} in and: item", value))?;
+            Ok(())
+        }
+        _ => Err(format!("Unknown and: item key {:?}", key)),
+    }
+}
+
+fn set_hexdump(pattern: &mut PendingPattern, value: &str) -> Result<(), String> {
+    if pattern.bytes.is_some() {
+        return Err("Duplicate hexdump".to_string());
+    }
+    let (bytes, wildcards): (Vec<u8>, Vec<u8>) =
+        parse_hex("hexdump", string("hexdump", value)?, true)?
+            .into_iter()
+            .unzip();
+    if bytes.is_empty() {
+        return Err("Empty hexdump".to_string());
+    }
+    pattern.bytes = Some(bytes);
+    pattern.wildcards = wildcards;
+    Ok(())
+}
+
+fn set_mask(pattern: &mut PendingPattern, value: &str) -> Result<(), String> {
+    let mask = parse_hex("mask", string("mask", value)?, false)?;
+    pattern.mask = Some(mask.into_iter().map(|(byte, _)| byte).collect());
+    Ok(())
+}
+
+/// A double-quoted string. Escapes are not supported, so it cannot contain
+/// `"` or `\`.
+fn string<'a>(key: &str, value: &'a str) -> Result<&'a str, String> {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .filter(|text| !text.contains(['"', '\\']))
+        .ok_or_else(|| format!("Invalid {} {}, expected a double-quoted string", key, value))
+}
+
+fn integer<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid {} {:?}, expected a decimal integer", key, value))
+}
+
+fn format_id(value: &str) -> Result<String, String> {
+    if !is_format_id(value) {
+        return Err(format!(
+            "Invalid format id {:?}, expected snake_case such as seven_zip",
+            value
+        ));
+    }
+    Ok(value.to_string())
+}
+
+/// Combines a pattern's wildcards with its explicit `mask`, normalizing the
+/// bytes so masked-out bits are zero. The mask is `None` for exact matches.
+fn finish_pattern(pending: PendingPattern, context: &str) -> Result<Option<PatternData>, String> {
+    let Some(mut bytes) = pending.bytes else {
+        return Ok(None);
+    };
+
+    let mut mask = pending.wildcards;
+    if let Some(explicit) = &pending.mask {
+        if explicit.len() != bytes.len() {
+            return Err(format!(
+                "Mask for {} has {} bytes, hexdump has {}",
+                context,
+                explicit.len(),
+                bytes.len()
+            ));
+        }
+        for (m, e) in mask.iter_mut().zip(explicit) {
+            *m &= e;
+        }
+    }
+    for (b, m) in bytes.iter_mut().zip(&mask) {
+        *b &= m;
+    }
+
+    let mask = if mask.iter().all(|&m| m == 0xFF) {
+        None
+    } else {
+        Some(mask)
+    };
+    Ok(Some(PatternData {
+        bytes,
+        mask,
+        offset: pending.offset,
+    }))
+}
+
+/// How many bits of the file a pattern constrains.
+pub(crate) fn significant_bits(pattern: &PatternData) -> u32 {
+    match &pattern.mask {
+        Some(mask) => mask.iter().map(|m| m.count_ones()).sum(),
+        None => pattern.bytes.len() as u32 * 8,
+    }
+}
+
+/// Files a finished signature under its `(extension, category)` entry.
+///
+/// Signatures constraining fewer than 16 bits across all their patterns are
+/// dropped to avoid false positives, unless they are only trusted alongside a
+/// matching extension. Exact duplicates within an entry
+/// (the YAML repeats some keys) are skipped.
+fn push_signature(
+    entries: &mut Vec<Entry>,
+    ext: &str,
+    category_mime: &str,
+    pending: PendingSig,
+) -> Result<(), String> {
+    let Some(description) = pending.description else {
+        return Err(format!(
+            "line {}: signature under {} has no description",
+            pending.line, ext
+        ));
+    };
+    let context = format!("\"{}\" under {} (line {})", description, ext, pending.line);
+    let Some(primary) = finish_pattern(pending.pattern, &context)? else {
+        return Err(format!("Signature {} has no hexdump", context));
+    };
+    let and = pending
+        .and
+        .into_iter()
+        .map(|p| {
+            finish_pattern(p, &context)?.ok_or_else(|| format!("Empty and: item for {}", context))
+        })
+        .collect::<Result<Vec<PatternData>, String>>()?;
+
+    let reach = and
+        .iter()
+        .map(|p| p.offset.checked_add(p.bytes.len()))
+        .chain([Some(primary.bytes.len())])
+        .try_fold(0, |reach: usize, end| Some(reach.max(end?)))
+        .and_then(|end| primary.offset.checked_add(pending.search)?.checked_add(end));
+    if reach.is_none_or(|reach| reach > MAX_REACH) {
+        return Err(format!(
+            "Signature {} reaches further than {} bytes into the file",
+            context, MAX_REACH
+        ));
+    }
+
+    let significant = significant_bits(&primary) + and.iter().map(significant_bits).sum::<u32>();
+    if significant == 0 || (significant < 16 && !pending.extension_required) {
+        return Ok(());
+    }
+    if pending.from_end && pending.search > 0 {
+        return Err(format!(
+            "Signature {} combines a negative offset with offset_range",
+            context
+        ));
+    }
+    if pending.from_end && primary.offset < primary.bytes.len() {
+        return Err(format!(
+            "Signature {} runs past the end of the file: offset -{} with {} bytes",
+            context,
+            primary.offset,
+            primary.bytes.len()
+        ));
+    }
+
+    let entry = entry_mut(entries, ext, category_mime, pending.line);
+    let sig = SigData {
+        bytes: primary.bytes,
+        mask: primary.mask,
+        offset: primary.offset,
+        from_end: pending.from_end,
+        search: pending.search,
+        and,
+        extension_required: pending.extension_required,
+        format: pending.format.unwrap_or_else(|| entry.format.clone()),
+        mime_type: pending.mime_type,
+        description,
+        hexdump_str: pending.hexdump_str,
+    };
+
+    let duplicate = entry.signatures.iter().any(|s| {
+        s.same_pattern(&sig)
+            && s.extension_required == sig.extension_required
+            && s.description == sig.description
+    });
+    if !duplicate {
+        entry.signatures.push(sig);
+    }
+    Ok(())
+}
+
+/// Returns the `(extension, category)` entry, creating it if needed so that
+/// extensions without any signatures are still known.
+fn entry_mut<'a>(
+    entries: &'a mut Vec<Entry>,
+    ext: &str,
+    category_mime: &str,
+    line: usize,
+) -> &'a mut Entry {
+    let index = match entries
+        .iter()
+        .position(|e| e.ext == ext && e.category_mime == category_mime)
+    {
+        Some(index) => index,
+        None => {
+            entries.push(Entry {
+                ext: ext.to_string(),
+                category_mime: category_mime.to_string(),
+                line,
+                format: key_format_id(ext).unwrap_or_default(),
+                mime_type: None,
+                description: None,
+                signatures: Vec::new(),
+            });
+            entries.len() - 1
+        }
+    };
+    &mut entries[index]
+}
+
+/// Whether `id` is a valid format id: lowercase snake case whose words each
+/// start with a letter, such as `seven_zip` or `rar5`, so that it maps to a
+/// `Format` variant name and back.
+pub(crate) fn is_format_id(id: &str) -> bool {
+    id.split('_').all(|word| {
+        word.starts_with(|c: char| c.is_ascii_lowercase())
+            && word
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    })
+}
+
+/// The format id of an extension key without a `format` field: its
+/// dot-separated parts in snake case, so `.tar.gz` gives `tar_gz`. `None`
+/// if that is not a valid id, as for `.7z`.
+pub(crate) fn key_format_id(ext: &str) -> Option<String> {
+    let id = ext
+        .to_lowercase()
+        .split('.')
+        .map(|part| {
+            part.chars()
+                .filter(char::is_ascii_alphanumeric)
+                .collect::<String>()
+        })
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+    is_format_id(&id).then_some(id)
+}
+
+/// Whether an extension key uses glob wildcards (`?`, `#` or `*`).
+pub(crate) fn is_pattern(ext: &str) -> bool {
+    ext.contains(['?', '#', '*'])
+}
+
+/// Whether a glob key is too loose to recognize files by name alone, because
+/// fewer than three of its characters are literal: `.?Q?` would also cover
+/// `.sql`. Such keys only match through their `extension_required`
+/// signatures.
+pub(crate) fn needs_magic(ext: &str) -> bool {
+    is_pattern(ext)
+        && ext
+            .trim_start_matches('.')
+            .chars()
+            .filter(|c| !matches!(c, '?' | '#' | '*'))
+            .count()
+            < 3
+}
+
+/// Whether a description names an extension as a word, like
+/// "ZIP compressed archive" does for `.zip`.
+fn describes(description: &str, ext: &str) -> bool {
+    let name = ext.trim_start_matches('.');
+    description
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|word| word.eq_ignore_ascii_case(name))
+}
+
+/// Every extension whose entry lists the same pattern as `sig`, canonical
+/// one first.
+///
+/// A pattern shared by several entries (ZIP under .zip, .jar, .apk, ...)
+/// belongs to the extension its description names, or else to the first
+/// entry listing it.
+pub(crate) fn owners(entries: &[Entry], sig: &SigData) -> Vec<String> {
+    let owners: Vec<(&str, &SigData)> = entries
+        .iter()
+        .flat_map(|entry| entry.signatures.iter().map(|s| (entry.ext.as_str(), s)))
+        .filter(|(_, s)| s.same_pattern(sig))
+        .collect();
+    let canonical = owners
+        .iter()
+        .find(|(ext, s)| describes(&s.description, ext))
+        .unwrap_or(&owners[0])
+        .0;
+    let mut extensions = vec![canonical.to_string()];
+    for (ext, _) in owners {
+        if !extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)) {
+            extensions.push(ext.to_string());
+        }
+    }
+    extensions
+}
+
+/// The IANA media type of each format id, from the `mime_type` fields of
+/// the extensions and signatures of that format. They must agree.
+pub(crate) fn mime_types(entries: &[Entry]) -> Result<HashMap<String, String>, String> {
+    let mut mime_types: HashMap<String, String> = HashMap::new();
+    let declared = entries.iter().flat_map(|entry| {
+        let own = entry.mime_type.as_ref().map(|mime| (&entry.format, mime));
+        let signatures = entry
+            .signatures
+            .iter()
+            .filter_map(|sig| sig.mime_type.as_ref().map(|mime| (&sig.format, mime)));
+        own.into_iter().chain(signatures)
+    });
+    for (format, mime_type) in declared {
+        if let Some(existing) = mime_types.get(format)
+            && existing != mime_type
+        {
+            return Err(format!(
+                "Conflicting mime_type for format {}: {} and {}",
+                format, existing, mime_type
+            ));
+        }
+        mime_types.insert(format.clone(), mime_type.clone());
+    }
+    Ok(mime_types)
+}
+
+/// What an extension key reports when only the extension is recognized.
+pub(crate) struct KeyInfo {
+    pub(crate) category_mime: String,
+    pub(crate) format: String,
+    pub(crate) description: String,
+}
+
+/// The [`KeyInfo`] of each lowercased extension key.
+///
+/// The description is the entry's own, else that of the key's first
+/// signature of the key's own format. An extension listed in several sections
+/// takes the most preferred category.
+pub(crate) fn extension_infos(entries: &[Entry]) -> BTreeMap<String, KeyInfo> {
+    let rank = |category: &str| {
+        CATEGORY_PRIORITY
+            .iter()
+            .position(|&p| p == category)
+            .unwrap_or(999)
+    };
+
+    let mut ext_to_info: BTreeMap<String, KeyInfo> = BTreeMap::new();
+    for entry in entries {
+        let own = entry
+            .signatures
+            .iter()
+            .find(|sig| sig.format == entry.format);
+        let description = match (&entry.description, own) {
+            (Some(description), _) => description.clone(),
+            (None, Some(sig)) => sig.description.clone(),
+            (None, None) => format!("{} archive", entry.ext),
+        };
+
+        let ext_lower = entry.ext.to_lowercase();
+        let replace = match ext_to_info.get(&ext_lower) {
+            Some(existing) => rank(&entry.category_mime) < rank(&existing.category_mime),
+            None => true,
+        };
+        if replace {
+            ext_to_info.insert(
+                ext_lower,
+                KeyInfo {
+                    category_mime: entry.category_mime.clone(),
+                    format: entry.format.clone(),
+                    description,
+                },
+            );
+        }
+    }
+    ext_to_info
+}
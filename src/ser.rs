@@ -0,0 +1,37 @@
+//! `serde::Serialize` implementations, enabled by the `serde` feature.
+//!
+//! The field names and values match the objects printed by the CLI's
+//! `--json` and `--ndjson` modes, minus the per-file `path`, `detected` and
+//! `error` fields.
+
+use crate::{ArchiveInfo, Format, Method};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+impl Serialize for ArchiveInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ArchiveInfo", 10)?;
+        state.serialize_field("format", &self.format)?;
+        state.serialize_field("category", self.category)?;
+        state.serialize_field("mime_type", &self.mime_type)?;
+        state.serialize_field("description", self.description)?;
+        state.serialize_field("hexdump", self.hexdump)?;
+        state.serialize_field("extension", self.extension)?;
+        state.serialize_field("extensions", self.extensions)?;
+        state.serialize_field("confidence", &self.confidence)?;
+        state.serialize_field("offset", &self.offset)?;
+        state.serialize_field("method", &self.method())?;
+        state.end()
+    }
+}
+
+impl Serialize for Format {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.id())
+    }
+}
+
+impl Serialize for Method {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
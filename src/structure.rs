@@ -0,0 +1,113 @@
+//! Lightweight structural checks run after a magic number matches.
+//!
+//! A magic number only says the first few bytes look right. For the most
+//! common formats we can cheaply validate a little more of the header, which
+//! separates real archives from files that merely start with the same bytes.
+
+use crate::magic::{MagicSignature, Sample};
+
+/// Validates the header around a signature matched at `start`.
+///
+/// Returns `None` when no check exists for the signature or `data` is too
+/// short to run it, otherwise whether the header is well formed.
+pub fn validate(sig: &MagicSignature, data: &Sample, start: usize) -> Option<bool> {
+    if sig.from_end {
+        return match sig.bytes {
+            [0x50, 0x4B, 0x05, 0x06] => zip_end_of_central_directory(data, start),
+            b"koly" => udif_trailer(data, start),
+            _ => None,
+        };
+    }
+
+    match (sig.bytes, sig.offset) {
+        ([0x1F, 0x8B, ..], 0) => gzip(data),
+        ([0x42, 0x5A, 0x68], 0) => bzip2(data),
+        ([0x50, 0x4B, 0x03, 0x04, ..], _) => zip_local_header(data, start),
+        ([0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C, ..], 0) => seven_zip(data),
+        ([0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00], 0) => xz(data),
+        ([0x28, 0xB5, 0x2F, 0xFD], 0) => zstd(data),
+        (b"ustar", 257) => tar(data),
+        (b"CD001", 1..) => iso9660(data, start - 1),
+        _ => None,
+    }
+}
+
+fn gzip(data: &Sample) -> Option<bool> {
+    let header = data.get(0, 10)?;
+    // Compression method must be deflate and the reserved flag bits clear.
+    Some(header[2] == 8 && header[3] & 0xE0 == 0)
+}
+
+fn bzip2(data: &Sample) -> Option<bool> {
+    let level = data.get(3, 1)?[0];
+    Some((b'1'..=b'9').contains(&level))
+}
+
+fn zip_local_header(data: &Sample, start: usize) -> Option<bool> {
+    let header = data.get(start, 30)?;
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    let method = u16::from_le_bytes([header[8], header[9]]);
+    let known_method = matches!(method, 0..=20 | 93..=99);
+    Some(version <= 100 && known_method)
+}
+
+fn seven_zip(data: &Sample) -> Option<bool> {
+    // Only major version 0 has ever been released.
+    let major = data.get(6, 1)?[0];
+    Some(major == 0)
+}
+
+fn xz(data: &Sample) -> Option<bool> {
+    let flags = data.get(6, 2)?;
+    Some(flags[0] == 0 && flags[1] & 0xF0 == 0)
+}
+
+fn zstd(data: &Sample) -> Option<bool> {
+    let descriptor = data.get(4, 1)?[0];
+    // Bit 3 of the frame header descriptor is reserved.
+    Some(descriptor & 0x08 == 0)
+}
+
+fn tar(data: &Sample) -> Option<bool> {
+    let header = data.get(0, 512)?;
+    let field = std::str::from_utf8(&header[148..156]).ok()?;
+    let digits = field.trim_matches(|c: char| c == ' ' || c == '\0');
+    let Ok(stored) = u32::from_str_radix(digits, 8) else {
+        return Some(false);
+    };
+    // The checksum is computed with its own field treated as spaces.
+    let computed: u32 = header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| {
+            if (148..156).contains(&i) {
+                0x20
+            } else {
+                b as u32
+            }
+        })
+        .sum();
+    Some(stored == computed)
+}
+
+fn iso9660(data: &Sample, start: usize) -> Option<bool> {
+    let descriptor = data.get(start, 7)?;
+    let valid_type = matches!(descriptor[0], 0..=3 | 255);
+    Some(valid_type && descriptor[6] == 1)
+}
+
+fn zip_end_of_central_directory(data: &Sample, start: usize) -> Option<bool> {
+    let record = data.get(start, 22)?;
+    let disk_entries = u16::from_le_bytes([record[8], record[9]]);
+    let total_entries = u16::from_le_bytes([record[10], record[11]]);
+    let comment_len = u16::from_le_bytes([record[20], record[21]]) as u64;
+    let remaining = data.len() - (start as u64 + 22);
+    Some(disk_entries <= total_entries && comment_len == remaining)
+}
+
+fn udif_trailer(data: &Sample, start: usize) -> Option<bool> {
+    let trailer = data.get(start, 12)?;
+    let version = u32::from_be_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    let header_size = u32::from_be_bytes([trailer[8], trailer[9], trailer[10], trailer[11]]);
+    Some(version == 4 && header_size == 512)
+}
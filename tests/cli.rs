@@ -0,0 +1,478 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn isarchive(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_isarchive"))
+        .args(args)
+        .output()
+        .expect("Failed to run isarchive")
+}
+
+/// Creates a fresh scratch directory for one test.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("isarchive_cli_{}", name));
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_json_output() {
+    let dir = scratch_dir("json");
+    let zip = dir.join("a.zip");
+    let text = dir.join("notes \"1\".txt");
+    fs::write(&zip, [0x50, 0x4B, 0x03, 0x04, 0x14, 0x00]).unwrap();
+    fs::write(&text, "hello").unwrap();
+    let missing = dir.join("missing.bin");
+
+    let output = isarchive(&[
+        "--json",
+        zip.to_str().unwrap(),
+        text.to_str().unwrap(),
+        missing.to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+
+    // JSON is valid YAML, so the YAML dev-dependency doubles as a parser.
+    let value: serde_yaml::Value = serde_yaml::from_slice(&output.stdout).unwrap();
+    let results = value.as_sequence().unwrap();
+    assert_eq!(results.len(), 3);
+
+    assert_eq!(results[0]["detected"], true);
+    assert_eq!(results[0]["format"], "zip");
+    assert_eq!(results[0]["method"], "magic");
+    assert_eq!(results[0]["offset"], 0);
+    assert!(results[0]["error"].is_null());
+
+    assert_eq!(results[1]["path"], text.to_str().unwrap());
+    assert_eq!(results[1]["detected"], false);
+    assert!(results[1]["error"].is_null());
+
+    assert_eq!(results[2]["detected"], false);
+    assert_eq!(results[2]["error"], "No such file or directory");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_ndjson_output() {
+    let dir = scratch_dir("ndjson");
+    let empty = dir.join("empty.tar.gz");
+    fs::write(&empty, []).unwrap();
+
+    let output = isarchive(&["--ndjson", empty.to_str().unwrap(), dir.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(3));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    let first: serde_yaml::Value = serde_yaml::from_str(lines[0]).unwrap();
+    assert_eq!(first["method"], "extension");
+    assert_eq!(first["format"], "tar_gz");
+    let second: serde_yaml::Value = serde_yaml::from_str(lines[1]).unwrap();
+    assert!(second["error"].as_str().unwrap().starts_with("cannot open"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_recursive_scan() {
+    let dir = scratch_dir("recursive");
+    fs::create_dir_all(dir.join("sub/deeper")).unwrap();
+    fs::create_dir_all(dir.join(".cache")).unwrap();
+    let xz = [0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00, 0x00, 0x04];
+    fs::write(dir.join("a.zip"), [0x50, 0x4B, 0x03, 0x04]).unwrap();
+    fs::write(dir.join("sub/b.xz"), xz).unwrap();
+    fs::write(dir.join("sub/deeper/c.xz"), xz).unwrap();
+    fs::write(dir.join(".cache/d.xz"), xz).unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&dir, dir.join("sub/loop")).unwrap();
+
+    let scan = |args: &[&str]| -> Vec<String> {
+        let mut full = vec!["-r", "--ndjson"];
+        full.extend_from_slice(args);
+        full.push(dir.to_str().unwrap());
+        let output = isarchive(&full);
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let value: serde_yaml::Value = serde_yaml::from_str(line).unwrap();
+                let path = PathBuf::from(value["path"].as_str().unwrap());
+                let relative = path.strip_prefix(&dir).unwrap().to_str().unwrap();
+                relative.replace('\\', "/")
+            })
+            .collect()
+    };
+
+    assert_eq!(scan(&[]), ["a.zip", "sub/b.xz", "sub/deeper/c.xz"]);
+    assert_eq!(
+        scan(&["--hidden"]),
+        [".cache/d.xz", "a.zip", "sub/b.xz", "sub/deeper/c.xz"]
+    );
+    assert_eq!(scan(&["--max-depth", "2"]), ["a.zip", "sub/b.xz"]);
+    assert_eq!(scan(&["--include=*.xz"]), ["sub/b.xz", "sub/deeper/c.xz"]);
+    assert_eq!(scan(&["--exclude", "deeper"]), ["a.zip", "sub/b.xz"]);
+    // The symlink leads back to the root, which is only scanned once.
+    assert_eq!(
+        scan(&["--follow-symlinks"]),
+        ["a.zip", "sub/b.xz", "sub/deeper/c.xz"]
+    );
+
+    // Without -r a directory cannot be opened.
+    let output = isarchive(&[dir.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(3));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_recursive_scan_non_utf8_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = scratch_dir("recursive_non_utf8");
+    let zip = dir.join(OsStr::from_bytes(b"caf\xe9.zip"));
+    fs::write(&zip, [0x50, 0x4B, 0x03, 0x04]).unwrap();
+
+    let output = isarchive(&["-r", "-b", dir.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, b"archive/compressed-archive\n");
+
+    // Listings keep the name's bytes as they are.
+    let output = isarchive(&["-r", "-0", "--only-archives", dir.to_str().unwrap()]);
+    let mut expected = zip.as_os_str().as_bytes().to_vec();
+    expected.push(0);
+    assert_eq!(output.stdout, expected);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_parallel_jobs_keep_order() {
+    let dir = scratch_dir("jobs");
+    let mut args = vec!["--ndjson".to_string()];
+    for i in 0..50 {
+        let path = dir.join(format!("{:02}.bin", i));
+        let data: &[u8] = if i % 3 == 0 { b"text" } else { b"PK\x03\x04" };
+        fs::write(&path, data).unwrap();
+        args.push(path.to_str().unwrap().to_string());
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let sequential = isarchive(&args);
+    let mut parallel_args = vec!["-j", "8"];
+    parallel_args.extend_from_slice(&args);
+    let parallel = isarchive(&parallel_args);
+
+    assert_eq!(parallel.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(parallel.stdout).unwrap(),
+        String::from_utf8(sequential.stdout).unwrap()
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_files_from_list() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let dir = scratch_dir("files_from");
+    let zip = dir.join("with space.zip");
+    let text = dir.join("notes.txt");
+    fs::write(&zip, [0x50, 0x4B, 0x03, 0x04]).unwrap();
+    fs::write(&text, "hello").unwrap();
+
+    let list = dir.join("list.txt");
+    fs::write(
+        &list,
+        format!("{}\r\n\n{}\n", zip.display(), text.display()),
+    )
+    .unwrap();
+    let output = isarchive(&["-b", "--files-from", list.to_str().unwrap()]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "archive/compressed-archive\nnot an archive\n"
+    );
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_isarchive"))
+        .args(["-0", "-b", "--files-from", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let listing = format!("{}\0{}\0", text.display(), zip.display());
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(listing.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "not an archive\narchive/compressed-archive\n"
+    );
+
+    // Names that are not UTF-8 are opened as listed.
+    #[cfg(unix)]
+    {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let latin1 = dir.join(OsStr::from_bytes(b"caf\xe9.zip"));
+        fs::write(&latin1, [0x50, 0x4B, 0x03, 0x04]).unwrap();
+        let mut listing = latin1.as_os_str().as_bytes().to_vec();
+        listing.push(b'\n');
+        fs::write(&list, listing).unwrap();
+        let output = isarchive(&["-b", "--files-from", list.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(output.stdout, b"archive/compressed-archive\n");
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fifo_input() {
+    use std::process::Stdio;
+
+    let dir = scratch_dir("fifo");
+    let fifo = dir.join("pipe");
+    let made = Command::new("mkfifo").arg(&fifo).status().unwrap();
+    assert!(made.success());
+
+    // A FIFO cannot seek, so it is read like standard input.
+    let child = Command::new(env!("CARGO_BIN_EXE_isarchive"))
+        .arg(&fifo)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    fs::write(&fifo, [0x50, 0x4B, 0x03, 0x04, 0x14, 0x00]).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("ZIP compressed archive")
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_stdin_input() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let run = |args: &[&str], data: &[u8]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_isarchive"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(data).unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let output = run(&["-i", "-"], &[0x1F, 0x8B, 0x08, 0x00]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "(standard input): archive/stream-compression\n"
+    );
+
+    let output = run(&["--ndjson", "-"], &b"plain text".repeat(10_000));
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\"path\":\"(standard input)\""));
+    assert!(stdout.contains("\"detected\":false"));
+
+    let output = isarchive(&["--files-from", "-", "-"]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_category_filters() {
+    let dir = scratch_dir("category");
+    let zip = dir.join("a.zip");
+    let gz = dir.join("b.gz");
+    let text = dir.join("c.txt");
+    fs::write(&zip, [0x50, 0x4B, 0x03, 0x04]).unwrap();
+    fs::write(&gz, [0x1F, 0x8B, 0x08, 0x00]).unwrap();
+    fs::write(&text, "hello").unwrap();
+    let paths = [
+        zip.to_str().unwrap(),
+        gz.to_str().unwrap(),
+        text.to_str().unwrap(),
+    ];
+    let with = |options: &[&str]| {
+        let mut args = options.to_vec();
+        args.extend(paths);
+        isarchive(&args)
+    };
+
+    let output = with(&["-i", "--category", "compressed-archive"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{}: archive/compressed-archive\n", paths[0])
+    );
+
+    let output = with(&[
+        "--only-archives",
+        "--exclude-category",
+        "archive/compressed-archive",
+    ]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{}\n", paths[1])
+    );
+
+    let output = with(&["--only-non-archives", "-0"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{}\0", paths[2])
+    );
+
+    let output = with(&["--category", "nonsense"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("Unknown category")
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_summary() {
+    let dir = scratch_dir("summary");
+    fs::write(dir.join("a.zip"), [0x50, 0x4B, 0x03, 0x04, 0x14, 0x00]).unwrap();
+    fs::write(dir.join("b.zip"), [0x50, 0x4B, 0x03, 0x04]).unwrap();
+    fs::write(dir.join("c.tar.gz"), []).unwrap();
+    fs::write(dir.join("d.txt"), "hello").unwrap();
+
+    let output = isarchive(&["--summary", "--json", "-r", dir.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    let summary: serde_yaml::Value = serde_yaml::from_slice(&output.stdout).unwrap();
+    assert_eq!(summary["files"], 4);
+    assert_eq!(summary["archives"], 3);
+    assert_eq!(summary["not_archives"], 1);
+    assert_eq!(
+        summary["categories"]["archive/compressed-archive"]["files"],
+        3
+    );
+    assert_eq!(
+        summary["categories"]["archive/compressed-archive"]["bytes"],
+        10
+    );
+    assert_eq!(summary["formats"]["zip"], 2);
+    assert_eq!(summary["formats"]["tar_gz"], 1);
+    let extension_only = summary["extension_only"].as_sequence().unwrap();
+    assert_eq!(extension_only.len(), 1);
+    assert!(extension_only[0].as_str().unwrap().ends_with("c.tar.gz"));
+
+    let output = isarchive(&["--summary", "-r", dir.to_str().unwrap()]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("4 files: 3 archives, 1 not archives, 0 errors\n"));
+    assert!(stdout.contains("Detected by extension only:"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_check_extension_exit_status() {
+    let dir = scratch_dir("check_extension");
+    let plain = dir.join("plain.tar.gz");
+    let mut tar = vec![0u8; 512];
+    tar[257..263].copy_from_slice(b"ustar\0");
+    fs::write(&plain, &tar).unwrap();
+    let missing = dir.join("missing.zip");
+
+    let output = isarchive(&["--check-extension", plain.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(5));
+
+    // Errors win over findings, whatever their order.
+    let output = isarchive(&[
+        "--check-extension",
+        plain.to_str().unwrap(),
+        missing.to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_list_formats() {
+    let output = isarchive(&["--list-formats", "--json"]);
+    assert_eq!(output.status.code(), Some(0));
+    let value: serde_yaml::Value = serde_yaml::from_slice(&output.stdout).unwrap();
+    let formats = value.as_sequence().unwrap();
+    let zip = formats.iter().find(|f| f["format"] == "zip").unwrap();
+    assert_eq!(zip["extension"], ".zip");
+    assert_eq!(zip["mime_type"], "application/zip");
+    let tar_gz = formats.iter().find(|f| f["format"] == "tar_gz").unwrap();
+    assert_eq!(tar_gz["extensions"][1], ".tgz");
+
+    let output = isarchive(&["--list-formats"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("FORMAT "));
+    assert_eq!(stdout.lines().count(), formats.len() + 1);
+
+    // Loaded formats are not listed, so the combination is refused.
+    let output = isarchive(&["--list-formats", "--signatures", "inhouse.yaml"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_custom_signatures() {
+    let dir = scratch_dir("signatures");
+    let signatures = dir.join("inhouse.yaml");
+    fs::write(
+        &signatures,
+        "packaging:\n  .acpk:\n    signatures:\n      - description: \"ACME package\"\n        hexdump: \"41 43 50 4B\"\n",
+    )
+    .unwrap();
+    let bundle = dir.join("bundle.bin");
+    fs::write(&bundle, b"ACPK\x00\x01").unwrap();
+    let bundle = bundle.to_str().unwrap();
+
+    let output = isarchive(&[bundle]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = isarchive(&[
+        "--signatures",
+        signatures.to_str().unwrap(),
+        "--ndjson",
+        bundle,
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    let value: serde_yaml::Value = serde_yaml::from_slice(&output.stdout).unwrap();
+    assert_eq!(value["format"], "custom");
+    assert_eq!(value["description"], "ACME package");
+    assert_eq!(value["category"], "archive/package");
+
+    let invalid = dir.join("invalid.yaml");
+    fs::write(&invalid, "packaging:\n  .acpk:\n    mime_type: oops\n").unwrap();
+    let output = isarchive(&["--signatures", invalid.to_str().unwrap(), bundle]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("line 3: Invalid mime_type")
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
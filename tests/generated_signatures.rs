@@ -8,7 +8,41 @@ use std::path::PathBuf;
 struct Signature {
     description: String,
     hexdump: String,
-    offset: Option<usize>,
+    mask: Option<String>,
+    offset: Option<i64>,
+    offset_range: Option<[i64; 2]>,
+    and: Option<Vec<Pattern>>,
+    extension_required: Option<bool>,
+}
+
+#[derive(serde::Deserialize)]
+struct Pattern {
+    hexdump: String,
+    mask: Option<String>,
+    offset: i64,
+}
+
+fn parse_hex(hexdump: &str) -> Vec<u8> {
+    // `??` wildcards are written as zero
+    hexdump
+        .split_whitespace()
+        .map(|s| match s {
+            "??" => 0,
+            _ => u8::from_str_radix(s, 16).expect("Invalid hex"),
+        })
+        .collect()
+}
+
+/// Bits of the file a pattern constrains, counting `??` and masked-out bits
+/// as free.
+fn significant_bits(hexdump: &str, mask: Option<&str>) -> u32 {
+    let mask = mask.map(parse_hex);
+    hexdump
+        .split_whitespace()
+        .enumerate()
+        .filter(|(_, s)| *s != "??")
+        .map(|(i, _)| mask.as_ref().map_or(8, |mask| mask[i].count_ones()))
+        .sum()
 }
 
 #[derive(serde::Deserialize)]
@@ -45,26 +79,38 @@ fn test_all_signatures_generated() {
         for (ext, entry) in extensions {
             if let Some(signatures) = entry.signatures {
                 for (i, sig) in signatures.iter().enumerate() {
-                    // Parse hex
-                    let bytes: Vec<u8> = sig
-                        .hexdump
-                        .split_whitespace()
-                        .map(|s| u8::from_str_radix(s, 16).expect("Invalid hex"))
-                        .collect();
-
-                    if bytes.len() < 2 {
-                        // build.rs ignores signatures shorter than 2 bytes to avoid false positives
+                    let bytes = parse_hex(&sig.hexdump);
+                    let extras = sig.and.as_deref().unwrap_or_default();
+                    let extension_required = sig.extension_required.unwrap_or(false);
+
+                    let significant = significant_bits(&sig.hexdump, sig.mask.as_deref())
+                        + extras
+                            .iter()
+                            .map(|extra| significant_bits(&extra.hexdump, extra.mask.as_deref()))
+                            .sum::<u32>();
+                    if significant < 16 && !extension_required {
+                        // build.rs ignores signatures under 16 bits to avoid false positives
                         continue;
                     }
 
                     // Use a generic extension to force Magic detection, NOT extension detection
                     // We append the original extension to the filename for debugging visibility,
-                    // but end with .bin or .tmp
+                    // but end with .bin or .tmp. Signatures that require their extension keep it,
+                    // and the result must then come from the magic match.
                     let safe_ext = ext.strip_prefix('.').unwrap_or(&ext);
-                    let filename = format!("{}_{}_{}.bin", category, safe_ext, i);
+                    let filename = if extension_required {
+                        format!("{}_{}_{}{}", category, safe_ext, i, ext)
+                    } else {
+                        format!("{}_{}_{}.bin", category, safe_ext, i)
+                    };
                     let file_path = test_dir.join(&filename);
 
-                    let offset = sig.offset.unwrap_or(0);
+                    // Ranged signatures are placed at the start of their window
+                    let offset = sig
+                        .offset_range
+                        .map(|[start, _]| start)
+                        .or(sig.offset)
+                        .unwrap_or(0);
 
                     // Create file
                     {
@@ -73,12 +119,26 @@ fn test_all_signatures_generated() {
                             file.seek(SeekFrom::Start(offset as u64)).unwrap();
                         }
                         file.write_all(&bytes).unwrap();
+                        for extra in extras {
+                            let at = offset.max(0) + extra.offset;
+                            file.seek(SeekFrom::Start(at as u64)).unwrap();
+                            file.write_all(&parse_hex(&extra.hexdump)).unwrap();
+                        }
+                        if offset < 0 {
+                            // End-anchored: pad so the signature sits `-offset` bytes from the end
+                            file.set_len(offset.unsigned_abs()).unwrap();
+                        }
                     }
 
                     // Test
                     let result = analyze(&file_path);
 
-                    if result.is_none() {
+                    let detected = match &result {
+                        Some(info) => !extension_required || info.offset.is_some(),
+                        None => false,
+                    };
+
+                    if !detected {
                         failures.push(format!(
                             "Failed to detect {} ({}) \n  File: {:?}\n  Hex: {}\n  Offset: {}",
                             ext, sig.description, file_path, sig.hexdump, offset